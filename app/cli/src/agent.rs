@@ -9,6 +9,9 @@ use framework::exception::Exception;
 use rand::Rng;
 use serde_json::json;
 
+// used when model is not specified by command args or prompt file
+pub const DEFAULT_MODEL: &str = "gpt5";
+
 pub struct TestStruct {}
 
 pub fn load(path: &Path) -> Result<HashMap<String, Chat>, Exception> {
@@ -17,7 +20,7 @@ pub fn load(path: &Path) -> Result<HashMap<String, Chat>, Exception> {
     Ok(agent)
 }
 
-fn create_function_store() -> Result<FunctionStore, Exception> {
+pub fn create_function_store() -> Result<FunctionStore, Exception> {
     let mut store = FunctionStore::default();
     store.add(
         Function {
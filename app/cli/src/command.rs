@@ -1,2 +1,10 @@
+pub mod chat;
 pub mod complete;
 pub mod completion;
+pub mod config;
+pub mod embed;
+pub mod image;
+pub mod models;
+mod path;
+pub mod speak;
+pub mod transcribe;
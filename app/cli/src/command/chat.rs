@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::Write;
+use std::io::stdout;
+use std::path::Path;
+use std::path::PathBuf;
+use std::slice;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use ::agent::openai::chat_api::ChatRequestMessage;
+use ::agent::openai::chat_api::Role;
+use ::agent::openai::session::Message;
+use ::agent::openai::session::Session;
+use clap::Args;
+use framework::exception;
+use framework::exception::Exception;
+use rustyline::DefaultEditor;
+use rustyline::error::ReadlineError;
+use tokio::fs;
+use tokio_stream::StreamExt;
+use tracing::info;
+use tracing::warn;
+
+use crate::agent;
+use crate::command::complete::tool_blocks;
+
+const HELP: &str = r"/help                 show this message
+/quit                 quit the chat, ctrl-d also works
+/model [name]         switch model, or list models if name is not specified
+/file {path}          attach file
+/image {path}         attach image
+/usage                show token usage of current session
+/save {path}          save transcript as prompt file, which can be continued by `puppet complete`
+```                   start or end multiline input";
+
+#[derive(Args)]
+pub struct Chat {
+    #[arg(long, help = "conf path")]
+    conf: PathBuf,
+
+    #[arg(long, help = "model name", default_value = agent::DEFAULT_MODEL)]
+    model: String,
+
+    #[arg(long, help = "system message")]
+    system: Option<String>,
+
+    #[arg(long, help = "session file to continue, saved after every answer")]
+    session: Option<PathBuf>,
+}
+
+impl Chat {
+    pub async fn execute(&self) -> Result<(), Exception> {
+        let chats = agent::load(&self.conf)?;
+        if !chats.contains_key(&self.model) {
+            return Err(exception!(message = format!("model not found, name={}", self.model)));
+        }
+        let mut model = self.model.to_string();
+
+        let session = match &self.session {
+            Some(path) if path.exists() => Session::load(path)?,
+            _ => Session::default(),
+        };
+        let session = Arc::new(Mutex::new(session));
+        let mut transcript = Transcript::default();
+        if let Some(system) = &self.system {
+            let mut session = session.lock().unwrap();
+            if session
+                .messages()
+                .iter()
+                .any(|message| matches!(message.role, Role::System))
+            {
+                info!("session already has system message, skip --system");
+            } else {
+                session.add_message(Message::SystemMessage(system.to_string()))?;
+            }
+        }
+
+        println!("puppet chat, model={model}, type /help for usage");
+
+        let mut editor = DefaultEditor::new()?;
+        let history = history_path();
+        if let Some(history) = &history {
+            // history file doesn't exist on first run
+            let _ = editor.load_history(history);
+        }
+
+        while let Some(input) = read_input(&mut editor)? {
+            if let Some(command) = input.strip_prefix('/') {
+                let (name, arg) = command.split_once(' ').unwrap_or((command, ""));
+                let arg = arg.trim();
+                match name {
+                    "quit" => break,
+                    "help" => println!("{HELP}"),
+                    "model" => switch_model(&chats, &mut model, arg),
+                    "file" | "image" => {
+                        if let Err(error) = attach(&session, &mut transcript, name, arg).await {
+                            println!("failed to attach {name}, error={error:?}");
+                        }
+                    }
+                    "usage" => {
+                        let usage = session.lock().unwrap().usage.clone();
+                        println!(
+                            "prompt_tokens={}, completion_tokens={}, total_tokens={}",
+                            usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+                        );
+                    }
+                    "save" => {
+                        if arg.is_empty() {
+                            println!("path is required, e.g. /save chat.md");
+                        } else if let Err(error) = save(&session, &transcript, &model, arg).await {
+                            println!("failed to save transcript, error={error:?}");
+                        } else {
+                            println!("transcript saved, path={arg}");
+                        }
+                    }
+                    _ => println!("unknown command, type /help for usage"),
+                }
+                continue;
+            }
+
+            let start = session.lock().unwrap().messages().len();
+            session
+                .lock()
+                .unwrap()
+                .add_message(Message::UserMessage(input.to_string()))?;
+
+            let chat = &chats[&model];
+            if let Err(error) = generate(chat, &session).await {
+                // remove user message and partial tool calls, otherwise next turn sends two user messages in a row
+                session.lock().unwrap().truncate_messages(start);
+                println!("\nfailed to generate, error={error:?}");
+                continue;
+            }
+            if let Some(path) = &self.session {
+                session.lock().unwrap().save(path)?;
+            }
+        }
+
+        if let Some(history) = &history {
+            if let Some(directory) = history.parent() {
+                fs::create_dir_all(directory).await?;
+            }
+            editor.save_history(history)?;
+        }
+        Ok(())
+    }
+}
+
+// stream answer to stdout
+async fn generate(chat: &::agent::openai::chat::Chat, session: &Arc<Mutex<Session>>) -> Result<(), Exception> {
+    let mut stream = chat.generate_stream(session.clone()).await?;
+    while let Some(text) = stream.next().await {
+        print!("{}", text?);
+        stdout().flush()?;
+    }
+    Ok(())
+}
+
+// return None on ctrl-d
+fn read_input(editor: &mut DefaultEditor) -> Result<Option<String>, Exception> {
+    loop {
+        let line = match editor.readline("> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut input = line;
+        if input.matches("```").count() % 2 == 1 {
+            loop {
+                let line = match editor.readline("... ") {
+                    Ok(line) => line,
+                    Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
+                    Err(error) => return Err(error.into()),
+                };
+                input.push('\n');
+                input.push_str(&line);
+                if line.contains("```") {
+                    break;
+                }
+            }
+        }
+        editor.add_history_entry(input.as_str())?;
+        return Ok(Some(input));
+    }
+}
+
+fn switch_model(chats: &HashMap<String, ::agent::openai::chat::Chat>, model: &mut String, name: &str) {
+    if name.is_empty() {
+        let mut names: Vec<&String> = chats.keys().collect();
+        names.sort();
+        for name in names {
+            let current = if name == model { "*" } else { " " };
+            println!("{current} {name}");
+        }
+    } else if chats.contains_key(name) {
+        *model = name.to_string();
+        println!("switched model, model={model}");
+    } else {
+        println!("model not found, name={name}");
+    }
+}
+
+async fn attach(
+    session: &Arc<Mutex<Session>>,
+    transcript: &mut Transcript,
+    kind: &str,
+    path: &str,
+) -> Result<(), Exception> {
+    if path.is_empty() {
+        return Err(exception!(message = "path is required"));
+    }
+    // use absolute path, so saved transcript works wherever it is saved
+    let path = fs::canonicalize(Path::new(path)).await?;
+    let path_value = path.to_string_lossy().to_string();
+    let mut session = session.lock().unwrap();
+    let index = session.messages().len();
+    if kind == "image" {
+        session.add_message(Message::Images(vec![path]))?;
+        transcript
+            .attachments
+            .insert(index, format!("> ![@img]=({path_value})"));
+    } else {
+        session.add_message(Message::Files(vec![path]))?;
+        transcript
+            .attachments
+            .insert(index, format!("> [@file]=({path_value})"));
+    }
+    println!("attached {kind}, path={path_value}");
+    Ok(())
+}
+
+fn history_path() -> Option<PathBuf> {
+    env::var("HOME")
+        .ok()
+        .map(|home| PathBuf::from(home).join(".config/puppet/chat_history"))
+}
+
+async fn save(
+    session: &Arc<Mutex<Session>>,
+    transcript: &Transcript,
+    model: &str,
+    path: &str,
+) -> Result<(), Exception> {
+    let markdown = transcript.render(model, session.lock().unwrap().messages())?;
+    fs::write(path, markdown).await?;
+    Ok(())
+}
+
+// render session in prompt file format, so earlier turns of continued session are included
+#[derive(Default)]
+struct Transcript {
+    attachments: HashMap<usize, String>, // message index to directive, session only keeps encoded content
+}
+
+impl Transcript {
+    fn render(&self, model: &str, messages: &[ChatRequestMessage]) -> Result<String, Exception> {
+        let mut markdown = format!("# system model={model}\n\n");
+        let mut current = Some("system");
+        for (index, message) in messages.iter().enumerate() {
+            if message.tool_calls.is_some() || message.tool_call_id.is_some() {
+                markdown.push_str(&tool_blocks(slice::from_ref(message))?);
+                current = None;
+                continue;
+            }
+            let role = match message.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+                Role::Tool => continue,
+            };
+            if current != Some(role) {
+                markdown.push_str(&format!("\n# {role}\n\n"));
+                current = Some(role);
+            }
+            if let Some(directive) = self.attachments.get(&index) {
+                markdown.push_str(directive);
+                markdown.push('\n');
+                continue;
+            }
+            for content in message.content.iter().flatten() {
+                match &content.text {
+                    Some(text) => {
+                        markdown.push_str(text);
+                        if !text.ends_with('\n') {
+                            markdown.push('\n');
+                        }
+                    }
+                    // attachment of earlier run, original path is unknown
+                    None => warn!("skip attachment in transcript, type={:?}", content.r#type),
+                }
+            }
+        }
+        Ok(markdown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::agent::openai::session::Message;
+    use ::agent::openai::session::Session;
+
+    use super::Transcript;
+
+    #[test]
+    fn render_session_history() {
+        let mut session = Session::default();
+        session
+            .add_message(Message::SystemMessage("be brief".to_string()))
+            .unwrap();
+        session.add_message(Message::UserMessage("hello".to_string())).unwrap();
+        session
+            .add_message(Message::AssistantMessage("hi".to_string()))
+            .unwrap();
+        session.add_message(Message::UserMessage("again".to_string())).unwrap();
+
+        let transcript = Transcript::default();
+        assert_eq!(
+            transcript.render("gpt", session.messages()).unwrap(),
+            "# system model=gpt\n\nbe brief\n\n# user\n\nhello\n\n# assistant\n\nhi\n\n# user\n\nagain\n"
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::env;
 use std::io::Write;
 use std::io::stdout;
 use std::mem;
@@ -5,177 +8,1025 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
+use ::agent::openai::cache::Cache;
+use ::agent::openai::chat::Chat;
+use ::agent::openai::chat_api::ChatRequestMessage;
+use ::agent::openai::chat_api::ResponseFormat;
+use ::agent::openai::chat_api::ToolCall;
+use ::agent::openai::chat_api::Usage;
 use ::agent::openai::session::Message;
 use ::agent::openai::session::Session;
+use ::agent::openai::transcription::Transcription;
 use clap::Args;
 use framework::exception;
 use framework::exception::Exception;
+use framework::json;
+use futures::StreamExt;
+use futures::stream;
 use glob::glob;
-use regex::Regex;
+use serde_json::json;
 use tokio::fs;
-use tokio::io::AsyncBufReadExt;
+use tokio::io;
+use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
-use tokio::io::BufReader;
-use tokio_stream::StreamExt;
+use tracing::debug;
+use tracing::info;
+use tracing::warn;
+use uuid::Uuid;
 
 use crate::agent;
+use crate::command::complete::attachment::FileMode;
+use crate::command::complete::markdown::Block;
+use crate::command::complete::markdown::Content;
+use crate::command::complete::markdown::Directive;
+use crate::command::complete::markdown::Header;
+use crate::command::complete::markdown::ResponseFormatSetting;
+use crate::command::complete::markdown::Settings;
+use crate::command::complete::template::Template;
+use crate::command::path::prompt_directory;
+use crate::command::path::resolve_pattern;
+
+mod attachment;
+mod markdown;
+mod template;
 
 #[derive(Args)]
 pub struct Complete {
-    #[arg(help = "prompt file path")]
-    prompt: PathBuf,
+    #[arg(
+        required = true,
+        help = "prompt file paths or glob patterns, - to read prompt from stdin"
+    )]
+    prompts: Vec<String>,
+
+    #[arg(
+        long,
+        help = "max number of prompt files completed concurrently",
+        default_value_t = 4
+    )]
+    parallelism: usize,
 
     #[arg(long, help = "conf path")]
     conf: PathBuf,
+
+    #[arg(long, help = "write answer to output file instead of appending to prompt file")]
+    output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "print answer to stdout only, do not write any file",
+        conflicts_with = "output",
+        default_value_t = false
+    )]
+    no_write: bool,
+
+    #[arg(
+        long,
+        help = "regenerate last assistant message, replace it instead of appending another one, implies --no-cache",
+        default_value_t = false
+    )]
+    replace_last: bool,
+
+    #[arg(
+        long,
+        help = "transcription model name, used by > [@transcribe] directive, transcript is cached as <audio>.transcript.txt"
+    )]
+    transcription_model: Option<String>,
+
+    #[arg(
+        long,
+        help = "session file to continue, saved after completion, prompt file must only contain new messages, e.g. use with --output"
+    )]
+    session: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "functions to expose to model, separated by comma, override functions in prompt file"
+    )]
+    functions: Vec<String>,
+
+    #[arg(
+        long = "var",
+        value_name = "KEY=VALUE",
+        help = "template variable, can be specified multiple times"
+    )]
+    vars: Vec<String>,
+
+    #[arg(long, help = "template variables file, json object of string values")]
+    vars_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "allow > [@cmd] directive to run shell commands",
+        default_value_t = false
+    )]
+    allow_cmd: bool,
+
+    #[arg(long, help = "timeout of > [@cmd] directive in seconds", default_value_t = 30)]
+    cmd_timeout: u64,
+
+    #[arg(long, help = "cache responses even if temperature is not 0", default_value_t = false)]
+    cache: bool,
+
+    #[arg(
+        long,
+        help = "do not read or write response cache",
+        conflicts_with = "cache",
+        default_value_t = false
+    )]
+    no_cache: bool,
+
+    #[arg(long, help = "ttl of cached responses in seconds", default_value_t = 7 * 24 * 3600)]
+    cache_ttl: u64,
 }
 
 impl Complete {
     pub async fn execute(&self) -> Result<(), Exception> {
-        let chats = agent::load(&self.conf)?;
+        let mut chats = agent::load(&self.conf)?;
+        if let Some(cache) = self.response_cache() {
+            chats = chats
+                .into_iter()
+                .map(|(name, chat)| (name, chat.with_cache(cache.clone())))
+                .collect();
+        }
+        let transcriptions = if self.transcription_model.is_some() {
+            ::agent::load_transcriptions(&self.conf)?
+        } else {
+            HashMap::new()
+        };
+        let transcription = self
+            .transcription_model
+            .as_ref()
+            .map(|name| {
+                transcriptions
+                    .get(name)
+                    .ok_or_else(|| exception!(message = format!("transcription model not found, name={name}")))
+            })
+            .transpose()?;
+        let template = Template::new(self.vars()?)?;
 
-        let prompt = fs::OpenOptions::new().read(true).open(&self.prompt).await?;
-        let reader = BufReader::new(prompt);
-        let mut lines = reader.lines();
+        let paths = self.prompt_paths()?;
+        if paths.len() == 1 {
+            let summary = self.complete(&paths[0], &chats, transcription, &template, true).await?;
+            if let Status::Skipped = summary.status {
+                info!(
+                    "last message is assistant message, skip, path={}",
+                    paths[0].to_string_lossy()
+                );
+            }
+            return Ok(());
+        }
 
-        let mut session = Session::default();
-        let mut parser = Parser::new(&mut session, &self.prompt);
+        if self.session.is_some() {
+            return Err(exception!(message = "--session only supports single prompt file"));
+        }
+        if self.output.is_some() {
+            return Err(exception!(message = "--output only supports single prompt file"));
+        }
+        // answers are not printed in batch mode, they would be discarded
+        if self.no_write {
+            return Err(exception!(message = "--no-write only supports single prompt file"));
+        }
+        let results: Vec<(&PathBuf, Result<Summary, Exception>)> = stream::iter(&paths)
+            .map(|path| {
+                let chats = &chats;
+                let template = &template;
+                async move {
+                    let result = self.complete(path, chats, transcription, template, false).await;
+                    (path, result)
+                }
+            })
+            .buffered(self.parallelism.max(1))
+            .collect()
+            .await;
+
+        let mut failed = 0;
+        for (path, result) in results {
+            let path = path.to_string_lossy();
+            match result {
+                Ok(summary) => println!(
+                    "{path}\tmodel={}, prompt_tokens={}, completion_tokens={}, status={}",
+                    summary.model,
+                    summary.usage.prompt_tokens,
+                    summary.usage.completion_tokens,
+                    summary.status.name()
+                ),
+                Err(error) => {
+                    failed += 1;
+                    println!("{path}\tstatus=failed");
+                    warn!("failed to complete prompt file, path={path}, error={error:?}");
+                }
+            }
+        }
+        if failed > 0 {
+            return Err(exception!(
+                message = format!(
+                    "failed to complete prompt files, failed={failed}, total={}",
+                    paths.len()
+                )
+            ));
+        }
+        Ok(())
+    }
+
+    // responses are cached when temperature is 0, or always with --cache,
+    // regenerating must not return the cached answer it is meant to replace
+    fn response_cache(&self) -> Option<Arc<Cache>> {
+        if self.no_cache || self.replace_last {
+            return None;
+        }
+        let home = env::var("HOME").ok()?;
+        Some(Arc::new(Cache::new(
+            PathBuf::from(home).join(".cache/puppet/chat"),
+            Duration::from_secs(self.cache_ttl),
+            self.cache,
+        )))
+    }
 
-        while let Some(line) = lines.next_line().await? {
-            if line.is_empty() {
-                continue;
+    // expand glob patterns, keep order of arguments
+    fn prompt_paths(&self) -> Result<Vec<PathBuf>, Exception> {
+        if self.reads_stdin() {
+            if self.prompts.len() > 1 {
+                return Err(exception!(
+                    message = "stdin prompt can not be combined with other prompt files"
+                ));
+            }
+            return Ok(vec![PathBuf::from(STDIN)]);
+        }
+        let mut paths = vec![];
+        let mut canonical_paths = HashSet::new();
+        for pattern in &self.prompts {
+            let mut matched = false;
+            for entry in glob(pattern)? {
+                let path = entry?;
+                // same file may be matched via different paths, e.g. a.md and ./a.md
+                if canonical_paths.insert(path.canonicalize()?) {
+                    paths.push(path);
+                }
+                matched = true;
+            }
+            if !matched {
+                return Err(exception!(
+                    message = format!("prompt file not found, pattern={pattern}")
+                ));
             }
-            parser.process_line(&line).await?;
+        }
+        Ok(paths)
+    }
+
+    // stream answer to stdout only if print is true, concurrent completions would interleave
+    async fn complete(
+        &self,
+        path: &Path,
+        chats: &HashMap<String, Chat>,
+        transcription: Option<&Transcription>,
+        template: &Template,
+        print: bool,
+    ) -> Result<Summary, Exception> {
+        let mut blocks = self.load(path, template).await?;
+        let model = blocks
+            .iter()
+            .filter_map(|block| match &block.header {
+                Header::System(settings) => settings.model.clone(),
+                _ => None,
+            })
+            .next_back()
+            .unwrap_or(agent::DEFAULT_MODEL.to_string());
+        if let Some(Header::Assistant) = blocks.last().map(|block| &block.header)
+            && !self.replace_last
+        {
+            return Ok(Summary {
+                model,
+                usage: Usage::default(),
+                status: Status::Skipped,
+            });
+        }
+        let output = self.output(path, &mut blocks).await?;
+
+        let mut session = match &self.session {
+            Some(path) if path.exists() => Session::load(path)?,
+            _ => Session::default(),
+        };
+        if !session.messages().is_empty()
+            && let Some(block) = blocks.iter().find(|block| is_history(block))
+        {
+            return Err(exception!(
+                message = format!(
+                    "prompt file contains history, which is already in session, use --output or --no-write to keep prompt file for new messages only, path={}, line={}",
+                    block.location.path.to_string_lossy(),
+                    block.location.line
+                )
+            ));
+        }
+        let mut parser = Parser::new(&mut session, transcription);
+        parser.command_timeout = self.allow_cmd.then(|| Duration::from_secs(self.cmd_timeout));
+
+        for block in blocks.iter() {
+            parser.process_block(block).await?;
         }
         parser.add_message()?;
         if !matches!(parser.state, ParserState::User) {
             return Err(exception!(message = "last message must be user message"));
         }
+        if !self.functions.is_empty() {
+            parser.session.functions = Some(self.functions.clone());
+        }
 
-        let chat = chats
-            .get(&parser.model.unwrap_or("gpt5".to_string()))
-            .ok_or_else(|| exception!(message = ""))?;
+        let chat = chats.get(&model).ok_or_else(|| {
+            let mut names: Vec<&String> = chats.keys().collect();
+            names.sort();
+            exception!(
+                message = format!(
+                    "model not found, name={model}, available={names:?}, check config with `puppet config validate`"
+                )
+            )
+        })?;
 
+        let schema = parser.schema.take();
+        let usage = session.usage.clone();
         let session = Arc::new(Mutex::new(session));
-        let mut stream = chat.generate_stream(session).await?;
-        let mut prompt = fs::OpenOptions::new().append(true).open(&self.prompt).await?;
-        prompt.write_all("\n# assistant\n\n".as_bytes()).await?;
+        let start = session.lock().unwrap().messages().len();
+        let mut stream = chat.generate_stream(session.clone()).await?;
+        let mut writer: Option<AtomicWriter> = None;
+        let mut started = false;
+        let mut answer = String::new();
         while let Some(text) = stream.next().await {
             let text = text?;
-            print!("{text}");
-            stdout().flush()?;
-            prompt.write_all(text.as_bytes()).await?;
+            if !started {
+                writer = match &output {
+                    Output::Prompt(truncate) => {
+                        let mut content = fs::read_to_string(path).await?;
+                        if let Some(line) = truncate {
+                            content = truncate_lines(&content, *line);
+                        }
+                        // tool calls are processed before answer is streamed, write them ahead of answer
+                        content.push_str(&tool_blocks(&session.lock().unwrap().messages()[start..])?);
+                        content.push_str("\n# assistant\n\n");
+                        Some(AtomicWriter::create(path, &content).await?)
+                    }
+                    Output::File(path) => Some(AtomicWriter::create(path, "").await?),
+                    Output::None => None,
+                };
+                started = true;
+            }
+            if print {
+                print!("{text}");
+                stdout().flush()?;
+            }
+            if let Some(writer) = &mut writer {
+                writer.write(&text).await?;
+            }
+            answer.push_str(&text);
+        }
+        // invalid answer is not persisted, writer drops temp file on error
+        if let Some((schema_path, schema)) = schema {
+            validate_schema(&schema_path, &schema, &answer)?;
+        }
+        if let Some(writer) = writer {
+            writer.commit().await?;
+        }
+        let session = session.lock().unwrap();
+        if let Some(path) = &self.session {
+            session.save(path)?;
+        }
+        Ok(Summary {
+            model,
+            usage: Usage {
+                prompt_tokens: session.usage.prompt_tokens - usage.prompt_tokens,
+                completion_tokens: session.usage.completion_tokens - usage.completion_tokens,
+                total_tokens: session.usage.total_tokens - usage.total_tokens,
+            },
+            status: Status::Completed,
+        })
+    }
+
+    // with --replace-last, remove last answer and its tool calls from blocks, they are regenerated
+    async fn output(&self, path: &Path, blocks: &mut Vec<Block>) -> Result<Output, Exception> {
+        let write_prompt = self.output.is_none() && !self.no_write && path != Path::new(STDIN);
+        let mut truncate = None;
+        if self.replace_last {
+            let keep = blocks
+                .iter()
+                .rposition(|block| matches!(block.header, Header::System(_) | Header::User))
+                .map_or(0, |index| index + 1);
+            if let Some(block) = blocks.get(keep)
+                && write_prompt
+            {
+                if block.location.path != fs::canonicalize(path).await? {
+                    return Err(exception!(
+                        message = format!(
+                            "last assistant message is not in prompt file, path={}",
+                            block.location.path.to_string_lossy()
+                        )
+                    ));
+                }
+                truncate = Some(block.location.line);
+            }
+            blocks.truncate(keep);
+        }
+        Ok(match &self.output {
+            Some(output) => Output::File(output.to_path_buf()),
+            None if write_prompt => Output::Prompt(truncate),
+            None => Output::None,
+        })
+    }
+
+    // stdin prompt is read as a whole, plain text without headers is a single user message
+    async fn load(&self, path: &Path, template: &Template) -> Result<Vec<Block>, Exception> {
+        let lines = if path == Path::new(STDIN) {
+            let mut content = String::new();
+            io::stdin().read_to_string(&mut content).await?;
+            template
+                .load_content(&env::current_dir()?.join(STDIN), &content)
+                .await?
+        } else {
+            template.load(path).await?
+        };
+        markdown::parse(&lines)
+    }
+
+    // logs go to stderr when reading from stdin, stdout only contains answer
+    pub fn reads_stdin(&self) -> bool {
+        self.prompts.iter().any(|prompt| prompt == STDIN)
+    }
+
+    fn vars(&self) -> Result<HashMap<String, String>, Exception> {
+        let mut vars: HashMap<String, String> = match &self.vars_file {
+            Some(path) => json::load_file(path)?,
+            None => HashMap::new(),
+        };
+        for var in &self.vars {
+            let (key, value) = var
+                .split_once('=')
+                .ok_or_else(|| exception!(message = format!("invalid var, expected key=value, var={var}")))?;
+            vars.insert(key.to_string(), value.to_string());
+        }
+        Ok(vars)
+    }
+}
+
+// function names referenced by prompt file with line number, checked by config validate
+pub(super) fn referenced_functions(content: &str) -> Result<Vec<(usize, String)>, Exception> {
+    markdown::referenced_functions(content)
+}
+
+const STDIN: &str = "-";
+const STALE_TEMP_FILE_AGE: Duration = Duration::from_secs(3600);
+
+enum Output {
+    Prompt(Option<usize>), // append to prompt file, truncate from line if replacing last answer
+    File(PathBuf),
+    None,
+}
+
+struct Summary {
+    model: String,
+    usage: Usage, // tokens used by this completion
+    status: Status,
+}
+
+enum Status {
+    Completed,
+    Skipped, // last message is already answered
+}
+
+impl Status {
+    fn name(&self) -> &'static str {
+        match self {
+            Status::Completed => "completed",
+            Status::Skipped => "skipped",
         }
-        Ok(())
     }
 }
 
 struct Parser<'a> {
     state: ParserState,
     current_message: String,
-    model: Option<String>,
     session: &'a mut Session,
-    current_path: &'a Path,
+    transcription: Option<&'a Transcription>,
+    schema: Option<(PathBuf, serde_json::Value)>,
+    command_timeout: Option<Duration>, // None means > [@cmd] is not allowed
 }
 
 enum ParserState {
     System,
     User,
     Assistant,
+    ToolCall,
+    ToolResult(String),
 }
 
 impl<'a> Parser<'a> {
-    fn new(session: &'a mut Session, current_path: &'a Path) -> Self {
+    fn new(session: &'a mut Session, transcription: Option<&'a Transcription>) -> Self {
         Self {
             state: ParserState::User,
             current_message: String::new(),
-            model: None,
             session,
-            current_path,
+            transcription,
+            schema: None,
+            command_timeout: None,
         }
     }
 
-    async fn process_line(&mut self, line: &str) -> Result<(), Exception> {
-        if line.starts_with("# system") {
-            self.add_message()?;
-
-            let regex = Regex::new(r#"model=([^,]+)"#)?;
-            if let Some(captures) = regex.captures(line) {
-                let model = captures[1].to_string();
-                self.model = Some(model);
+    async fn process_block(&mut self, block: &Block) -> Result<(), Exception> {
+        self.add_message()?;
+        debug!("[complete] {}", block.header.render());
+        self.state = match &block.header {
+            Header::System(settings) => {
+                self.settings(settings);
+                ParserState::System
             }
-            let regex = Regex::new(r#"top_p=([^,]+)"#)?;
-            if let Some(captures) = regex.captures(line) {
-                let top_p = captures[1].parse()?;
-                self.session.top_p = Some(top_p);
+            Header::User => ParserState::User,
+            Header::Assistant => ParserState::Assistant,
+            Header::ToolCall => ParserState::ToolCall,
+            Header::ToolResult(id) => ParserState::ToolResult(id.to_string()),
+        };
+        for content in &block.contents {
+            match content {
+                Content::Text(text) => self.current_message.push_str(text),
+                Content::Directive(directive, location) => {
+                    debug!(
+                        "[complete] {}, path={}, line={}",
+                        directive.render(),
+                        location.path.to_string_lossy(),
+                        location.line
+                    );
+                    self.process_directive(directive, &location.path).await?
+                }
             }
+        }
+        Ok(())
+    }
 
-            self.state = ParserState::System;
-        } else if line.starts_with("# user") {
-            self.add_message()?;
-
-            self.state = ParserState::User;
-        } else if line.starts_with("> ![@img]") {
-            self.add_message()?;
+    // directives are resolved relative to the prompt file which declares them
+    async fn process_directive(&mut self, directive: &Directive, current_path: &Path) -> Result<(), Exception> {
+        match directive {
+            Directive::Image(pattern) => {
+                self.add_message()?;
 
-            let regex = Regex::new(r#"> ![@img]=\((.*)\)"#)?;
-            if let Some(captures) = regex.captures(line) {
                 let mut images = vec![];
-                let pattern = self.pattern(&captures[1]).await?;
-                for entry in glob(&pattern)? {
-                    let path = entry?;
-                    images.push(path);
+                for entry in glob(&resolve_pattern(current_path, pattern).await?)? {
+                    images.push(entry?);
                 }
                 self.session.add_message(Message::Images(images))?;
             }
-        } else if line.starts_with("> [@file]") {
-            self.add_message()?;
+            Directive::File(pattern, mode) => {
+                self.add_message()?;
 
-            let regex = Regex::new(r#"> [@file]=\((.*)\)"#)?;
-            if let Some(captures) = regex.captures(line) {
                 let mut files = vec![];
-                let pattern = self.pattern(&captures[1]).await?;
-                for entry in glob(&pattern)? {
+                for entry in glob(&resolve_pattern(current_path, pattern).await?)? {
                     let path = entry?;
-                    files.push(path);
+                    if *mode == FileMode::File {
+                        files.push(path);
+                        continue;
+                    }
+                    let content = fs::read(&path).await?;
+                    // keep order of matched files, flush inlined text before binary files and vice versa
+                    if *mode == FileMode::Auto && !attachment::is_text(&path, &content) {
+                        self.add_message()?;
+                        files.push(path);
+                        continue;
+                    }
+                    if !files.is_empty() {
+                        self.session.add_message(Message::Files(mem::take(&mut files)))?;
+                    }
+                    let content = String::from_utf8(content).map_err(|_| {
+                        exception!(message = format!("file is not utf-8 text, path={}", path.to_string_lossy()))
+                    })?;
+                    let name = display_name(current_path, &path).await?;
+                    self.current_message
+                        .push_str(&attachment::inline(&name, &path, &content));
+                }
+                if !files.is_empty() {
+                    self.session.add_message(Message::Files(files))?;
                 }
-                self.session.add_message(Message::Files(files))?;
             }
-        } else if line.starts_with("# assistant") {
-            self.add_message()?;
+            Directive::Directory(pattern, options) => {
+                self.add_message()?;
 
-            self.state = ParserState::Assistant;
-        } else {
-            self.current_message.push_str(line);
-            self.current_message.push('\n');
+                let path = PathBuf::from(resolve_pattern(current_path, pattern).await?);
+                let text = attachment::directory(&path, &prompt_directory(current_path).await?, options)?;
+                self.current_message.push_str(&text);
+            }
+            Directive::Command(command) => {
+                self.add_message()?;
+
+                let timeout = self.command_timeout.ok_or_else(|| {
+                    exception!(message = "> [@cmd] runs shell command, must be enabled by --allow-cmd")
+                })?;
+                let text = attachment::command(command, &prompt_directory(current_path).await?, timeout).await?;
+                self.session.add_message(Message::UserMessage(text))?;
+            }
+            Directive::Audio(pattern) => {
+                self.add_message()?;
+
+                let mut audios = vec![];
+                for entry in glob(&resolve_pattern(current_path, pattern).await?)? {
+                    audios.push(entry?);
+                }
+                self.session.add_message(Message::Audio(audios))?;
+            }
+            Directive::Schema(pattern) => {
+                let path = PathBuf::from(resolve_pattern(current_path, pattern).await?);
+                let schema: serde_json::Value = json::from_json(&fs::read_to_string(&path).await?)?;
+                // name must match ^[a-zA-Z0-9_-]+$
+                let name: String = path
+                    .file_stem()
+                    .unwrap()
+                    .to_string_lossy()
+                    .chars()
+                    .map(|char| {
+                        if char.is_ascii_alphanumeric() || char == '-' {
+                            char
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                self.session.response_format = Some(ResponseFormat::json_schema(json!({
+                    "name": name,
+                    "schema": &schema,
+                    "strict": true
+                })));
+                self.schema = Some((path, schema));
+            }
+            Directive::Transcribe(pattern) => {
+                let transcription = self.transcription.ok_or_else(|| {
+                    exception!(
+                        message = "transcription model is required by > [@transcribe], use --transcription-model"
+                    )
+                })?;
+                for entry in glob(&resolve_pattern(current_path, pattern).await?)? {
+                    let path = entry?;
+                    let text = transcribe(transcription, &path).await?;
+                    self.current_message.push_str(&text);
+                    self.current_message.push('\n');
+                }
+            }
         }
         Ok(())
     }
 
+    fn settings(&mut self, settings: &Settings) {
+        if let Some(temperature) = settings.temperature {
+            self.session.temperature = Some(temperature);
+        }
+        if let Some(top_p) = settings.top_p {
+            self.session.top_p = Some(top_p);
+        }
+        if let Some(max_completion_tokens) = settings.max_completion_tokens {
+            self.session.max_completion_tokens = Some(max_completion_tokens);
+        }
+        if let Some(response_format) = settings.response_format {
+            self.session.response_format = Some(match response_format {
+                ResponseFormatSetting::Text => ResponseFormat::text(),
+                ResponseFormatSetting::JsonObject => ResponseFormat::json(),
+            });
+        }
+        if let Some(functions) = &settings.functions {
+            self.session.functions = Some(functions.clone());
+        }
+    }
+
     fn add_message(&mut self) -> Result<(), Exception> {
         let message = mem::take(&mut self.current_message);
         if !message.is_empty() {
-            match self.state {
+            match &self.state {
                 ParserState::System => self.session.add_message(Message::SystemMessage(message)),
                 ParserState::User => self.session.add_message(Message::UserMessage(message)),
                 ParserState::Assistant => self.session.add_message(Message::AssistantMessage(message)),
+                ParserState::ToolCall => {
+                    let calls: Vec<ToolCall> = json::from_json(code_block(&message))?;
+                    self.session.add_message(Message::ToolCalls(calls))
+                }
+                ParserState::ToolResult(id) => self.session.add_message(Message::ToolResult {
+                    id: id.to_string(),
+                    value: code_block(&message).to_string(),
+                }),
             }?;
         }
         Ok(())
     }
+}
+
+// transcript is kept next to audio, e.g. note.m4a.transcript.txt, so prompt reruns do not upload audio again,
+// it is regenerated when audio is modified after it
+async fn transcribe(transcription: &Transcription, path: &Path) -> Result<String, Exception> {
+    let name = path.file_name().unwrap().to_string_lossy();
+    let transcript_path = path.with_file_name(format!("{name}.transcript.txt"));
+    if let (Ok(audio), Ok(transcript)) = (fs::metadata(path).await, fs::metadata(&transcript_path).await)
+        && transcript.modified()? >= audio.modified()?
+    {
+        info!("use transcript, path={}", transcript_path.to_string_lossy());
+        return Ok(fs::read_to_string(&transcript_path).await?);
+    }
+    let text = transcription.transcribe(path, None, None).await?;
+    fs::write(&transcript_path, &text).await?;
+    Ok(text)
+}
 
-    async fn pattern(&self, pattern: &str) -> Result<String, Exception> {
-        if !pattern.starts_with('/') {
-            return Ok(format!(
-                "{}/{}",
-                fs::canonicalize(&self.current_path)
-                    .await?
-                    .parent()
-                    .unwrap()
-                    .to_string_lossy(),
-                pattern
-            ));
+// path relative to directory of current prompt file if possible
+async fn display_name(current_path: &Path, path: &Path) -> Result<String, Exception> {
+    let directory = prompt_directory(current_path).await?;
+    Ok(path
+        .strip_prefix(&directory)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .to_string())
+}
+
+// messages answered before, system message with content is kept in session as well
+fn is_history(block: &Block) -> bool {
+    match block.header {
+        Header::System(_) => !block.contents.is_empty(),
+        Header::User => false,
+        Header::Assistant | Header::ToolCall | Header::ToolResult(_) => true,
+    }
+}
+
+// remove lines from line number, keep one trailing newline
+fn truncate_lines(content: &str, line: usize) -> String {
+    let mut content = content.lines().take(line - 1).collect::<Vec<_>>().join("\n");
+    content.truncate(content.trim_end().len());
+    content.push('\n');
+    content
+}
+
+// write to temp file in same directory and rename on commit, target file is never left with partial answer,
+// temp file is removed on drop, if process is killed, stale temp file is removed by next write to same file
+struct AtomicWriter {
+    path: PathBuf,
+    temp_path: PathBuf,
+    file: fs::File,
+    committed: bool,
+}
+
+impl AtomicWriter {
+    async fn create(path: &Path, content: &str) -> Result<Self, Exception> {
+        // write to symlink target, rename would replace symlink itself
+        let path = if fs::symlink_metadata(path)
+            .await
+            .is_ok_and(|metadata| metadata.file_type().is_symlink())
+        {
+            fs::canonicalize(path).await?
+        } else {
+            path.to_path_buf()
+        };
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        remove_stale_temp_files(&path, &name).await;
+        let temp_path = path.with_file_name(format!(".{name}.{}.tmp", Uuid::now_v7()));
+        let mut file = fs::File::create(&temp_path).await?;
+        if let Ok(metadata) = fs::metadata(&path).await {
+            fs::set_permissions(&temp_path, metadata.permissions()).await?;
         }
-        Ok(pattern.to_string())
+        file.write_all(content.as_bytes()).await?;
+        Ok(AtomicWriter {
+            path,
+            temp_path,
+            file,
+            committed: false,
+        })
+    }
+
+    async fn write(&mut self, text: &str) -> Result<(), Exception> {
+        self.file.write_all(text.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn commit(mut self) -> Result<(), Exception> {
+        self.file.flush().await?;
+        self.file.sync_all().await?;
+        fs::rename(&self.temp_path, &self.path).await?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicWriter {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+// remove temp files left by killed processes, e.g. .prompt.md.<uuid>.tmp, recent ones may belong to running process
+async fn remove_stale_temp_files(path: &Path, name: &str) {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let Ok(mut entries) = fs::read_dir(directory).await else {
+        return;
+    };
+    let prefix = format!(".{name}.");
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !file_name.starts_with(&prefix) || !file_name.ends_with(".tmp") {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .await
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| modified.elapsed().is_ok_and(|elapsed| elapsed > STALE_TEMP_FILE_AGE));
+        if stale {
+            info!("remove stale temp file, path={}", entry.path().to_string_lossy());
+            let _ = fs::remove_file(entry.path()).await;
+        }
+    }
+}
+
+// render tool call and tool result messages as prompt file blocks
+pub(crate) fn tool_blocks(messages: &[ChatRequestMessage]) -> Result<String, Exception> {
+    let mut blocks = String::new();
+    for message in messages {
+        if let Some(calls) = &message.tool_calls {
+            let calls = serde_json::to_string_pretty(calls)?;
+            blocks.push_str(&format!("\n# tool_call\n\n```json\n{calls}\n```\n"));
+        } else if let Some(id) = &message.tool_call_id {
+            let value: String = message
+                .content
+                .iter()
+                .flatten()
+                .filter_map(|content| content.text.as_deref())
+                .collect();
+            blocks.push_str(&format!("\n# tool_result id={id}\n\n```json\n{value}\n```\n"));
+        }
+    }
+    Ok(blocks)
+}
+
+// validate answer against json schema, report mismatches in diff-like format
+fn validate_schema(schema_path: &Path, schema: &serde_json::Value, answer: &str) -> Result<(), Exception> {
+    let value: serde_json::Value = serde_json::from_str(code_block(answer))
+        .map_err(|error| exception!(message = format!("response is not valid json, error={error}")))?;
+    let validator = jsonschema::validator_for(schema)?;
+    let mut diff = String::new();
+    for error in validator.iter_errors(&value) {
+        let keyword = error.schema_path().as_str();
+        let expected = schema.pointer(keyword).unwrap_or(&serde_json::Value::Null);
+        diff.push_str(&format!(
+            "@ {}: {error}\n- expected: {keyword}={expected}\n+ actual: {}\n",
+            error.instance_path().as_str(),
+            error.instance()
+        ));
+    }
+    if !diff.is_empty() {
+        return Err(exception!(
+            message = format!(
+                "response does not match schema, schema={}\n{diff}",
+                schema_path.to_string_lossy()
+            )
+        ));
+    }
+    Ok(())
+}
+
+// strip fenced code block markers around block content
+fn code_block(message: &str) -> &str {
+    let message = message.trim();
+    if let Some(content) = message.strip_prefix("```")
+        && let Some((_, content)) = content.split_once('\n')
+    {
+        return content.trim_end().strip_suffix("```").unwrap_or(content).trim();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use ::agent::openai::chat_api::FunctionCall;
+    use ::agent::openai::chat_api::ToolCall;
+    use ::agent::openai::session::Message;
+    use ::agent::openai::session::Session;
+    use clap::Parser as _;
+    use tempfile::tempdir;
+
+    use super::AtomicWriter;
+    use super::Complete;
+    use super::Parser;
+    use super::markdown;
+    use super::template::Template;
+    use super::tool_blocks;
+
+    #[tokio::test]
+    async fn atomic_writer_keeps_symlink_and_permissions() {
+        let directory = tempdir().unwrap();
+        let target = directory.path().join("target.md");
+        let link = directory.path().join("link.md");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        let mut writer = AtomicWriter::create(&link, "new").await.unwrap();
+        writer.write(" answer").await.unwrap();
+        writer.commit().await.unwrap();
+
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new answer");
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    #[tokio::test]
+    async fn tool_blocks_round_trip() {
+        let mut session = Session::default();
+        session
+            .add_message(Message::ToolCalls(vec![ToolCall {
+                id: "call_1".to_string(),
+                r#type: "function".to_string(),
+                function: FunctionCall {
+                    name: "get_weather".to_string(),
+                    arguments: r#"{"city":"Paris \"FR\""}"#.to_string(),
+                },
+            }]))
+            .unwrap();
+        session
+            .add_message(Message::ToolResult {
+                id: "call_1".to_string(),
+                value: "{\n  \"temperature\": 20\n}".to_string(),
+            })
+            .unwrap();
+        let expected = serde_json::to_value(session.messages()).unwrap();
+
+        let content = format!("# user\n\nweather?\n{}", tool_blocks(session.messages()).unwrap());
+        let lines = Template::new(HashMap::new())
+            .unwrap()
+            .load_content(Path::new("/tmp/prompt.md"), &content)
+            .await
+            .unwrap();
+        let mut parsed = Session::default();
+        let mut parser = Parser::new(&mut parsed, None);
+        for block in markdown::parse(&lines).unwrap() {
+            parser.process_block(&block).await.unwrap();
+        }
+        parser.add_message().unwrap();
+
+        let actual = serde_json::to_value(&parsed.messages()[1..]).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[tokio::test]
+    async fn command_in_assistant_block_is_not_run() {
+        let directory = tempdir().unwrap();
+        let marker = directory.path().join("marker");
+        let content = format!(
+            "# user\n\nhi\n\n# assistant\n\n> [@cmd]=(touch {})\n",
+            marker.to_string_lossy()
+        );
+        let lines = Template::new(HashMap::new())
+            .unwrap()
+            .load_content(&directory.path().join("prompt.md"), &content)
+            .await
+            .unwrap();
+        let mut session = Session::default();
+        let mut parser = Parser::new(&mut session, None);
+        parser.command_timeout = Some(Duration::from_secs(5));
+        for block in markdown::parse(&lines).unwrap() {
+            parser.process_block(&block).await.unwrap();
+        }
+        parser.add_message().unwrap();
+
+        assert!(!marker.exists());
+        let text = session.messages()[1].content.as_ref().unwrap()[0].text.clone();
+        assert_eq!(
+            text.unwrap(),
+            format!("> [@cmd]=(touch {})\n", marker.to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn prompt_paths_deduplicate_same_file() {
+        #[derive(clap::Parser)]
+        struct Command {
+            #[command(flatten)]
+            complete: Complete,
+        }
+
+        let directory = tempdir().unwrap();
+        fs::write(directory.path().join("a.md"), "hi").unwrap();
+        let directory_value = directory.path().to_string_lossy();
+        let command = Command::parse_from([
+            "complete",
+            "--conf",
+            "agent.json",
+            &format!("{directory_value}/a.md"),
+            &format!("{directory_value}/./a.md"),
+            &format!("{directory_value}/*.md"),
+        ]);
+
+        assert_eq!(
+            command.complete.prompt_paths().unwrap(),
+            vec![directory.path().join("a.md")]
+        );
     }
 }
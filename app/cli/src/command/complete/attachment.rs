@@ -0,0 +1,380 @@
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use framework::exception;
+use framework::exception::Exception;
+use ignore::WalkBuilder;
+use tokio::process::Command;
+use tokio::time;
+use tracing::info;
+use tracing::warn;
+
+// bytes to check for binary content
+const SNIFF_SIZE: usize = 8192;
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "rs",
+    "md",
+    "txt",
+    "toml",
+    "json",
+    "yaml",
+    "yml",
+    "xml",
+    "html",
+    "css",
+    "js",
+    "ts",
+    "tsx",
+    "jsx",
+    "py",
+    "go",
+    "java",
+    "kt",
+    "kts",
+    "gradle",
+    "c",
+    "h",
+    "cpp",
+    "hpp",
+    "cs",
+    "rb",
+    "php",
+    "swift",
+    "sh",
+    "fish",
+    "sql",
+    "csv",
+    "ini",
+    "conf",
+    "properties",
+    "lock",
+    "gitignore",
+    "dockerfile",
+];
+
+const FILE_EXTENSIONS: &[&str] = &["pdf"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum FileMode {
+    Auto, // inline text files, attach others as file parts
+    Text,
+    File,
+}
+
+impl FileMode {
+    // e.g. mode=text
+    pub(super) fn parse(options: &str) -> Result<Self, String> {
+        match options.trim() {
+            "" | "mode=auto" => Ok(FileMode::Auto),
+            "mode=text" => Ok(FileMode::Text),
+            "mode=file" => Ok(FileMode::File),
+            options => Err(format!(
+                "invalid file options, expected mode=auto, mode=text or mode=file, options={options}"
+            )),
+        }
+    }
+
+    pub(super) fn render(&self) -> &'static str {
+        match self {
+            FileMode::Auto => "",
+            FileMode::Text => " mode=text",
+            FileMode::File => " mode=file",
+        }
+    }
+}
+
+pub(super) fn is_text(path: &Path, content: &[u8]) -> bool {
+    let extension = path
+        .extension()
+        .or(path.file_name())
+        .map(|extension| extension.to_string_lossy().trim_start_matches('.').to_lowercase());
+    if let Some(extension) = extension.as_deref() {
+        if FILE_EXTENSIONS.contains(&extension) {
+            return false;
+        }
+        // e.g. latin-1 csv, it is attached as file instead of failing the run
+        if TEXT_EXTENSIONS.contains(&extension) {
+            return std::str::from_utf8(content).is_ok();
+        }
+    }
+    let sniff = &content[..content.len().min(SNIFF_SIZE)];
+    !sniff.contains(&0) && std::str::from_utf8(content).is_ok()
+}
+
+// render file as fenced code block with file name header
+pub(super) fn inline(name: &str, path: &Path, content: &str) -> String {
+    let language = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("{name}\n{}", fenced(&language, content))
+}
+
+pub(super) fn fenced(language: &str, content: &str) -> String {
+    // use longer fence if content contains fence itself
+    let mut fence = "```".to_string();
+    while content.contains(&fence) {
+        fence.push('`');
+    }
+    let content = content.strip_suffix('\n').unwrap_or(content);
+    format!("{fence}{language}\n{content}\n{fence}\n")
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct DirectoryOptions {
+    pub(super) max_file_bytes: u64,
+    pub(super) max_total_bytes: u64,
+}
+
+impl Default for DirectoryOptions {
+    fn default() -> Self {
+        DirectoryOptions {
+            max_file_bytes: 256 * 1024,
+            max_total_bytes: 1024 * 1024,
+        }
+    }
+}
+
+impl DirectoryOptions {
+    // e.g. max_file_bytes=10000, max_total_bytes=100000
+    pub(super) fn parse(options: &str) -> Result<Self, String> {
+        let mut result = DirectoryOptions::default();
+        for option in options.split([',', ' ']).filter(|option| !option.is_empty()) {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("invalid option, expected key=value, option={option}"))?;
+            let value = value
+                .parse()
+                .map_err(|_| format!("invalid option value, key={key}, value={value}"))?;
+            match key {
+                "max_file_bytes" => result.max_file_bytes = value,
+                "max_total_bytes" => result.max_total_bytes = value,
+                _ => return Err(format!("unknown option, key={key}")),
+            }
+        }
+        Ok(result)
+    }
+
+    pub(super) fn render(&self) -> String {
+        let default = DirectoryOptions::default();
+        let mut options = String::new();
+        if self.max_file_bytes != default.max_file_bytes {
+            options.push_str(&format!(" max_file_bytes={}", self.max_file_bytes));
+        }
+        if self.max_total_bytes != default.max_total_bytes {
+            options.push_str(&format!(" max_total_bytes={}", self.max_total_bytes));
+        }
+        options
+    }
+}
+
+// walk directory recursively respecting .gitignore, return text files rendered as fenced blocks
+pub(super) fn directory(path: &Path, base: &Path, options: &DirectoryOptions) -> Result<String, Exception> {
+    let mut text = String::new();
+    let mut total_bytes = 0;
+    let mut included = 0;
+    let mut skipped = 0;
+
+    let walker = WalkBuilder::new(path)
+        .require_git(false)
+        .sort_by_file_path(|path1, path2| path1.cmp(path2))
+        .build();
+    for entry in walker {
+        let entry = entry?;
+        if !entry.file_type().is_some_and(|file_type| file_type.is_file()) {
+            continue;
+        }
+        let path = entry.path();
+        let name = path.strip_prefix(base).unwrap_or(path).to_string_lossy();
+
+        let size = entry.metadata()?.len();
+        if size > options.max_file_bytes {
+            info!("skip file, path={name}, reason=file too large, size={size}");
+            skipped += 1;
+            continue;
+        }
+        if total_bytes + size > options.max_total_bytes {
+            info!("skip file, path={name}, reason=total size exceeded, size={size}");
+            skipped += 1;
+            continue;
+        }
+        let content = fs::read(path)?;
+        if !is_text(path, &content) {
+            info!("skip file, path={name}, reason=binary");
+            skipped += 1;
+            continue;
+        }
+        let Ok(content) = String::from_utf8(content) else {
+            info!("skip file, path={name}, reason=not utf-8");
+            skipped += 1;
+            continue;
+        };
+
+        info!("include file, path={name}, size={size}");
+        text.push_str(&inline(&name, path, &content));
+        total_bytes += size;
+        included += 1;
+    }
+    info!(
+        "attach directory, path={}, included={included}, skipped={skipped}, total_bytes={total_bytes}",
+        path.to_string_lossy()
+    );
+    Ok(text)
+}
+
+// run shell command in directory, return command with its output
+pub(super) async fn command(command: &str, directory: &Path, timeout: Duration) -> Result<String, Exception> {
+    info!(
+        "run command, command={command}, directory={}",
+        directory.to_string_lossy()
+    );
+    // run in own process group, so timeout kills commands spawned by shell as well
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(directory)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true)
+        .spawn()?;
+    let pid = child.id();
+    let output = match time::timeout(timeout, child.wait_with_output()).await {
+        Ok(output) => output?,
+        Err(_) => {
+            if let Some(pid) = pid {
+                kill_process_group(pid).await;
+            }
+            return Err(exception!(
+                message = format!("command timed out, command={command}, timeout={timeout:?}")
+            ));
+        }
+    };
+
+    let mut text = format!("$ {command}\n");
+    text.push_str(&fenced("", &String::from_utf8_lossy(&output.stdout)));
+    if !output.stderr.is_empty() {
+        text.push_str("stderr\n");
+        text.push_str(&fenced("", &String::from_utf8_lossy(&output.stderr)));
+    }
+    if !output.status.success() {
+        text.push_str(&format!("exit status: {}\n", output.status));
+    }
+    Ok(text)
+}
+
+async fn kill_process_group(pid: u32) {
+    let result = Command::new("kill")
+        .args(["-KILL", "--", &format!("-{pid}")])
+        .status()
+        .await;
+    if let Err(error) = result {
+        warn!("failed to kill command process group, pid={pid}, error={error}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+    use tempfile::tempdir;
+    use tokio::process::Command;
+
+    use super::DirectoryOptions;
+
+    #[tokio::test]
+    async fn command_timeout_kills_process_group() {
+        let directory = tempdir().unwrap();
+
+        let result = super::command(
+            "sleep 30 & echo $! > pid; wait",
+            directory.path(),
+            Duration::from_millis(500),
+        )
+        .await;
+        assert!(result.is_err());
+        let pid = fs::read_to_string(directory.path().join("pid")).unwrap();
+        // killed process may stay as zombie until init reaps it
+        let output = Command::new("ps")
+            .args(["-o", "stat=", "-p", pid.trim()])
+            .output()
+            .await
+            .unwrap();
+        let stat = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stat.trim().is_empty() || stat.starts_with('Z'),
+            "background process is still running, pid={}, stat={stat}",
+            pid.trim()
+        );
+    }
+
+    #[test]
+    fn is_text_requires_utf8() {
+        assert!(super::is_text(
+            Path::new("data.csv"),
+            "name,city\nJosé,Paris".as_bytes()
+        ));
+        // latin-1 é
+        assert!(!super::is_text(Path::new("data.csv"), b"name,city\nJos\xe9,Paris"));
+        assert!(!super::is_text(Path::new("doc.pdf"), b"%PDF-1.7"));
+    }
+
+    fn create_directory(files: &[(&str, &[u8])]) -> TempDir {
+        let directory = tempdir().unwrap();
+        for (path, content) in files {
+            fs::write(directory.path().join(path), content).unwrap();
+        }
+        directory
+    }
+
+    fn attach_directory(directory: &TempDir, options: &DirectoryOptions) -> String {
+        super::directory(directory.path(), directory.path(), options).unwrap()
+    }
+
+    #[test]
+    fn directory_respects_gitignore() {
+        let directory = create_directory(&[
+            (".gitignore", b"ignored.txt\n"),
+            ("a.txt", b"a"),
+            ("ignored.txt", b"secret"),
+        ]);
+        let text = attach_directory(&directory, &DirectoryOptions::default());
+        assert_eq!(text, "a.txt\n```txt\na\n```\n");
+    }
+
+    #[test]
+    fn directory_skips_large_file() {
+        let directory = create_directory(&[("a.txt", b"a"), ("b.txt", b"bbbbbbbbbb")]);
+        let options = DirectoryOptions {
+            max_file_bytes: 5,
+            ..DirectoryOptions::default()
+        };
+        let text = attach_directory(&directory, &options);
+        assert_eq!(text, "a.txt\n```txt\na\n```\n");
+    }
+
+    #[test]
+    fn directory_stops_at_total_size() {
+        let directory = create_directory(&[("a.txt", b"aaa"), ("b.txt", b"bbb"), ("c.txt", b"c")]);
+        let options = DirectoryOptions {
+            max_total_bytes: 5,
+            ..DirectoryOptions::default()
+        };
+        // b.txt exceeds remaining budget, smaller file after it still fits
+        let text = attach_directory(&directory, &options);
+        assert_eq!(text, "a.txt\n```txt\naaa\n```\nc.txt\n```txt\nc\n```\n");
+    }
+
+    #[test]
+    fn directory_skips_binary_file() {
+        let directory = create_directory(&[("a.txt", b"a"), ("b.bin", b"\x00\x01\x02")]);
+        let text = attach_directory(&directory, &DirectoryOptions::default());
+        assert_eq!(text, "a.txt\n```txt\na\n```\n");
+    }
+}
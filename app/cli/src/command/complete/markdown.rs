@@ -0,0 +1,593 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use framework::exception;
+use framework::exception::Exception;
+use regex::Regex;
+
+use crate::command::complete::attachment::DirectoryOptions;
+use crate::command::complete::attachment::FileMode;
+use crate::command::complete::template::Line;
+
+#[derive(Debug)]
+pub(super) struct Block {
+    pub(super) header: Header,
+    pub(super) contents: Vec<Content>,
+    pub(super) location: Location, // where the header is declared, or first line of implicit user block
+}
+
+impl PartialEq for Block {
+    fn eq(&self, other: &Self) -> bool {
+        self.header == other.header && self.contents == other.contents
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) enum Header {
+    System(Settings),
+    User,
+    Assistant,
+    ToolCall,
+    ToolResult(String),
+}
+
+#[derive(Debug)]
+pub(super) enum Content {
+    Text(String),
+    Directive(Directive, Location),
+}
+
+// location is where the directive is declared, not part of equality
+impl PartialEq for Content {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Content::Text(text1), Content::Text(text2)) => text1 == text2,
+            (Content::Directive(directive1, _), Content::Directive(directive2, _)) => directive1 == directive2,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct Location {
+    pub(super) path: PathBuf,
+    pub(super) line: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) enum Directive {
+    Image(String),
+    File(String, FileMode),
+    Directory(String, DirectoryOptions),
+    Audio(String),
+    Schema(String),
+    Transcribe(String),
+    Command(String),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Settings {
+    pub(super) model: Option<String>,
+    pub(super) temperature: Option<f32>,
+    pub(super) top_p: Option<f32>,
+    pub(super) max_completion_tokens: Option<i32>,
+    pub(super) response_format: Option<ResponseFormatSetting>,
+    pub(super) functions: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum ResponseFormatSetting {
+    Text,
+    JsonObject,
+}
+
+impl ResponseFormatSetting {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(ResponseFormatSetting::Text),
+            "json_object" => Ok(ResponseFormatSetting::JsonObject),
+            _ => Err(format!(
+                "invalid response_format, expected text or json_object, value={value}"
+            )),
+        }
+    }
+
+    fn render(&self) -> &'static str {
+        match self {
+            ResponseFormatSetting::Text => "text",
+            ResponseFormatSetting::JsonObject => "json_object",
+        }
+    }
+}
+
+// tracks fenced code blocks, markdown inside them is plain text
+#[derive(Default)]
+pub(super) struct Fence {
+    open: Option<(char, usize, usize)>, // fence char, length and line number of opening fence
+}
+
+impl Fence {
+    // return true if line is inside fenced code block, including opening and closing fence
+    pub(super) fn process(&mut self, line: &str, number: usize) -> bool {
+        let trimmed = line.trim_start();
+        let marker = trimmed.chars().next().filter(|char| *char == '`' || *char == '~');
+        let length = marker.map_or(0, |marker| trimmed.chars().take_while(|char| *char == marker).count());
+        match (self.open, marker) {
+            (None, Some(marker)) if length >= 3 => {
+                self.open = Some((marker, length, number));
+                true
+            }
+            (Some((open_marker, open_length, _)), Some(marker))
+                if marker == open_marker && length >= open_length && trimmed[length..].trim().is_empty() =>
+            {
+                self.open = None;
+                true
+            }
+            (open, _) => open.is_some(),
+        }
+    }
+
+    pub(super) fn unclosed_line(&self) -> Option<usize> {
+        self.open.map(|(_, _, line)| line)
+    }
+}
+
+pub(super) fn parse(lines: &[Line]) -> Result<Vec<Block>, Exception> {
+    let header_regex = Regex::new(r"^# (system|user|assistant|tool_call|tool_result)(?:\s+(.*))?$")?;
+    let directive_regex = Regex::new(r"^> (!?)\[@(\w+)\]=\((.*)\)([^)]*)$")?;
+
+    let mut blocks: Vec<Block> = vec![];
+    let mut text: Vec<&str> = vec![];
+    let mut fence = Fence::default();
+
+    for line in lines {
+        // content before any header belongs to user block
+        if blocks.is_empty() && !line.text.trim().is_empty() && !header_regex.is_match(&line.text) {
+            blocks.push(Block {
+                header: Header::User,
+                contents: vec![],
+                location: location(line),
+            });
+        }
+
+        if fence.process(&line.text, line.number) {
+            text.push(&line.text);
+            continue;
+        }
+
+        if let Some(captures) = header_regex.captures(&line.text) {
+            flush_text(&mut blocks, &mut text);
+            let arguments = captures.get(2).map_or("", |arguments| arguments.as_str().trim());
+            let header = match &captures[1] {
+                "system" => Header::System(Settings::parse(arguments).map_err(|message| error(line, &message))?),
+                "tool_result" => Header::ToolResult(
+                    arguments
+                        .strip_prefix("id=")
+                        .filter(|id| !id.is_empty() && !id.contains(char::is_whitespace))
+                        .ok_or_else(|| error(line, "tool_result must have id, e.g. # tool_result id=call_1"))?
+                        .to_string(),
+                ),
+                name => {
+                    if !arguments.is_empty() {
+                        return Err(error(line, &format!("unexpected arguments of {name} header")));
+                    }
+                    match name {
+                        "user" => Header::User,
+                        "assistant" => Header::Assistant,
+                        _ => Header::ToolCall,
+                    }
+                }
+            };
+            blocks.push(Block {
+                header,
+                contents: vec![],
+                location: location(line),
+            });
+        } else if (line.text.starts_with("> [@") || line.text.starts_with("> ![@"))
+            // answers and tool results may quote directives, they must not be run or uploaded by next run
+            && blocks
+                .last()
+                .is_some_and(|block| matches!(block.header, Header::System(_) | Header::User))
+        {
+            let captures = directive_regex
+                .captures(&line.text)
+                .ok_or_else(|| error(line, "invalid directive, expected > [@name]=(argument)"))?;
+            let directive = Directive::parse(&captures[1], &captures[2], &captures[3], &captures[4])
+                .map_err(|message| error(line, &message))?;
+            flush_text(&mut blocks, &mut text);
+            blocks
+                .last_mut()
+                .unwrap()
+                .contents
+                .push(Content::Directive(directive, location(line)));
+        } else {
+            text.push(&line.text);
+        }
+    }
+    if let Some(number) = fence.unclosed_line() {
+        let line = lines.iter().find(|line| line.number == number).unwrap();
+        return Err(error(line, "code fence is not closed"));
+    }
+    flush_text(&mut blocks, &mut text);
+    Ok(blocks)
+}
+
+// function names of system headers outside fences with line number, templates and includes are not resolved
+pub(super) fn referenced_functions(content: &str) -> Result<Vec<(usize, String)>, Exception> {
+    let header_regex = Regex::new(r"^# system(?:\s+(.*))?$")?;
+    let mut fence = Fence::default();
+    let mut functions = vec![];
+    for (index, line) in content.lines().enumerate() {
+        if fence.process(line, index + 1) {
+            continue;
+        }
+        if let Some(captures) = header_regex.captures(line) {
+            let arguments = captures.get(1).map_or("", |arguments| arguments.as_str().trim());
+            let settings = Settings::parse(arguments)
+                .map_err(|message| exception!(message = format!("{message}, line={}", index + 1)))?;
+            for function in settings.functions.into_iter().flatten() {
+                functions.push((index + 1, function));
+            }
+        }
+    }
+    Ok(functions)
+}
+
+// prompt files are appended in place, render is used to verify round trip
+#[cfg(test)]
+pub(super) fn render(blocks: &[Block]) -> String {
+    let mut markdown = String::new();
+    for (index, block) in blocks.iter().enumerate() {
+        if index > 0 {
+            markdown.push('\n');
+        }
+        markdown.push_str(&block.header.render());
+        markdown.push_str("\n\n");
+        for (index, content) in block.contents.iter().enumerate() {
+            if index > 0 {
+                markdown.push('\n');
+            }
+            match content {
+                Content::Text(text) => markdown.push_str(text),
+                Content::Directive(directive, _) => {
+                    markdown.push_str(&directive.render());
+                    markdown.push('\n');
+                }
+            }
+        }
+    }
+    markdown
+}
+
+fn location(line: &Line) -> Location {
+    Location {
+        path: line.path.clone(),
+        line: line.number,
+    }
+}
+
+// blank lines around text are separators, blank lines inside are kept
+fn flush_text(blocks: &mut [Block], text: &mut Vec<&str>) {
+    let start = text.iter().position(|line| !line.trim().is_empty());
+    let end = text.iter().rposition(|line| !line.trim().is_empty());
+    if let (Some(start), Some(end)) = (start, end) {
+        let mut value = text[start..=end].join("\n");
+        value.push('\n');
+        blocks.last_mut().unwrap().contents.push(Content::Text(value));
+    }
+    text.clear();
+}
+
+fn error(line: &Line, message: &str) -> Exception {
+    exception!(message = format!("{message}, path={}, line={}", line.path.to_string_lossy(), line.number))
+}
+
+impl Header {
+    pub(super) fn render(&self) -> String {
+        match self {
+            Header::System(settings) => {
+                let settings = settings.render();
+                if settings.is_empty() {
+                    "# system".to_string()
+                } else {
+                    format!("# system {settings}")
+                }
+            }
+            Header::User => "# user".to_string(),
+            Header::Assistant => "# assistant".to_string(),
+            Header::ToolCall => "# tool_call".to_string(),
+            Header::ToolResult(id) => format!("# tool_result id={id}"),
+        }
+    }
+}
+
+impl Directive {
+    fn parse(image: &str, name: &str, argument: &str, options: &str) -> Result<Self, String> {
+        let argument = argument.to_string();
+        let options = options.trim();
+        if (image == "!") != (name == "img") {
+            return Err(format!(
+                "invalid directive, use > ![@img]=(path) for images, name={name}"
+            ));
+        }
+        if !options.is_empty() && !matches!(name, "file" | "dir") {
+            return Err(format!("unexpected options of {name} directive, options={options}"));
+        }
+        match name {
+            "img" => Ok(Directive::Image(argument)),
+            "file" => Ok(Directive::File(argument, FileMode::parse(options)?)),
+            "dir" => Ok(Directive::Directory(argument, DirectoryOptions::parse(options)?)),
+            "audio" => Ok(Directive::Audio(argument)),
+            "schema" => Ok(Directive::Schema(argument)),
+            "transcribe" => Ok(Directive::Transcribe(argument)),
+            "cmd" => Ok(Directive::Command(argument)),
+            _ => Err(format!("unknown directive, name={name}")),
+        }
+    }
+
+    pub(super) fn render(&self) -> String {
+        match self {
+            Directive::Image(argument) => format!("> ![@img]=({argument})"),
+            Directive::File(argument, mode) => format!("> [@file]=({argument}){}", mode.render()),
+            Directive::Directory(argument, options) => format!("> [@dir]=({argument}){}", options.render()),
+            Directive::Audio(argument) => format!("> [@audio]=({argument})"),
+            Directive::Schema(argument) => format!("> [@schema]=({argument})"),
+            Directive::Transcribe(argument) => format!("> [@transcribe]=({argument})"),
+            Directive::Command(argument) => format!("> [@cmd]=({argument})"),
+        }
+    }
+}
+
+impl Settings {
+    // grammar: key=value, key=value, list values are in brackets, e.g. functions=[a, b]
+    fn parse(settings: &str) -> Result<Self, String> {
+        let mut result = Settings::default();
+        for setting in split_settings(settings) {
+            let (key, value) = setting
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .ok_or_else(|| format!("invalid setting, expected key=value, setting={setting}"))?;
+            match key {
+                "model" => result.model = Some(value.to_string()),
+                "temperature" => result.temperature = Some(parse_setting(key, value)?),
+                "top_p" => result.top_p = Some(parse_setting(key, value)?),
+                "max_completion_tokens" => result.max_completion_tokens = Some(parse_setting(key, value)?),
+                "response_format" => result.response_format = Some(ResponseFormatSetting::parse(value)?),
+                "functions" => {
+                    let functions = value
+                        .strip_prefix('[')
+                        .and_then(|value| value.strip_suffix(']'))
+                        .unwrap_or(value);
+                    let functions: Vec<String> = functions
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|function| !function.is_empty())
+                        .map(str::to_string)
+                        .collect();
+                    // api rejects empty tools, omit functions setting to disable them
+                    if functions.is_empty() {
+                        return Err("functions must not be empty, remove functions setting instead".to_string());
+                    }
+                    result.functions = Some(functions);
+                }
+                _ => return Err(format!("unknown setting, key={key}")),
+            }
+        }
+        Ok(result)
+    }
+
+    fn render(&self) -> String {
+        let mut settings = vec![];
+        if let Some(model) = &self.model {
+            settings.push(format!("model={model}"));
+        }
+        if let Some(temperature) = self.temperature {
+            settings.push(format!("temperature={temperature}"));
+        }
+        if let Some(top_p) = self.top_p {
+            settings.push(format!("top_p={top_p}"));
+        }
+        if let Some(max_completion_tokens) = self.max_completion_tokens {
+            settings.push(format!("max_completion_tokens={max_completion_tokens}"));
+        }
+        if let Some(response_format) = self.response_format {
+            settings.push(format!("response_format={}", response_format.render()));
+        }
+        if let Some(functions) = &self.functions {
+            settings.push(format!("functions=[{}]", functions.join(", ")));
+        }
+        settings.join(", ")
+    }
+}
+
+// split by commas outside of brackets
+fn split_settings(settings: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, char) in settings.char_indices() {
+        match char {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(&settings[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(&settings[start..]);
+    result
+        .into_iter()
+        .map(str::trim)
+        .filter(|setting| !setting.is_empty())
+        .collect()
+}
+
+fn parse_setting<T: FromStr>(key: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid setting value, key={key}, value={value}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn lines(markdown: &str) -> Vec<Line> {
+        markdown
+            .lines()
+            .enumerate()
+            .map(|(index, text)| Line {
+                text: text.to_string(),
+                path: PathBuf::from("test.md"),
+                number: index + 1,
+            })
+            .collect()
+    }
+
+    fn round_trip(markdown: &str) -> Vec<Block> {
+        let blocks = parse(&lines(markdown)).unwrap();
+        let rendered = render(&blocks);
+        assert_eq!(parse(&lines(&rendered)).unwrap(), blocks, "rendered:\n{rendered}");
+        assert_eq!(render(&parse(&lines(&rendered)).unwrap()), rendered);
+        blocks
+    }
+
+    fn error_message(markdown: &str) -> String {
+        format!("{:?}", parse(&lines(markdown)).unwrap_err())
+    }
+
+    #[test]
+    fn round_trip_document() {
+        let blocks = round_trip(
+            r#"# system model=gpt5, temperature=0.2, top_p=0.9, max_completion_tokens=1000, response_format=json_object, functions=[get_random_number, close_door]
+
+you are a helpful assistant
+
+# user
+
+> ![@img]=(images/*.png)
+> [@file]=(src/*.rs) mode=text
+> [@dir]=(lib/agent) max_file_bytes=1000
+> [@audio]=(recording.wav)
+> [@schema]=(config.schema.json)
+> [@transcribe]=(memo.m4a)
+> [@cmd]=(git diff (HEAD))
+
+review the code
+
+# tool_call
+
+```json
+[{"id":"call_1","type":"function","function":{"name":"close_door","arguments":"{}"}}]
+```
+
+# tool_result id=call_1
+
+```json
+{"success":true}
+```
+
+# assistant
+
+done
+"#,
+        );
+        assert_eq!(blocks.len(), 5);
+        assert_eq!(
+            blocks[0].header,
+            Header::System(Settings {
+                model: Some("gpt5".to_string()),
+                temperature: Some(0.2),
+                top_p: Some(0.9),
+                max_completion_tokens: Some(1000),
+                response_format: Some(ResponseFormatSetting::JsonObject),
+                functions: Some(vec!["get_random_number".to_string(), "close_door".to_string()]),
+            })
+        );
+        assert_eq!(blocks[1].contents.len(), 8);
+        assert_eq!(
+            blocks[1].contents[6],
+            Content::Directive(
+                Directive::Command("git diff (HEAD)".to_string()),
+                Location {
+                    path: PathBuf::from("test.md"),
+                    line: 11
+                }
+            )
+        );
+        assert_eq!(blocks[3].header, Header::ToolResult("call_1".to_string()));
+    }
+
+    #[test]
+    fn preserve_blank_lines() {
+        let blocks = round_trip("# user\n\n\nfirst paragraph\n\n\nsecond paragraph\n\n");
+        assert_eq!(
+            blocks[0].contents,
+            vec![Content::Text("first paragraph\n\n\nsecond paragraph\n".to_string())]
+        );
+    }
+
+    #[test]
+    fn ignore_markdown_inside_code_fence() {
+        let blocks = round_trip(
+            "# user\n\n````markdown\n# user\n> [@file]=(a.rs)\n```\n# assistant\n```\n````\n\n# assistant\n\nanswer\n",
+        );
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[0].contents,
+            vec![Content::Text(
+                "````markdown\n# user\n> [@file]=(a.rs)\n```\n# assistant\n```\n````\n".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn content_before_header_is_user_message() {
+        let blocks = round_trip("\nhello\n\n# assistant\n\nanswer\n");
+        assert_eq!(blocks[0].header, Header::User);
+        assert_eq!(blocks[0].location.line, 2);
+        assert_eq!(blocks[1].location.line, 4);
+        assert_eq!(render(&blocks), "# user\n\nhello\n\n# assistant\n\nanswer\n");
+    }
+
+    #[test]
+    fn markdown_heading_is_text() {
+        let blocks = round_trip("# user\n\n# Title\n## user\n");
+        assert_eq!(
+            blocks[0].contents,
+            vec![Content::Text("# Title\n## user\n".to_string())]
+        );
+    }
+
+    #[test]
+    fn directive_in_history_is_text() {
+        let blocks = round_trip(
+            "# assistant\n\n> [@cmd]=(rm -rf /)\n> [@file]=(~/.ssh/id_rsa)\n\n# tool_result id=call_1\n\n> [@dir]=(/)\n",
+        );
+        assert_eq!(
+            blocks[0].contents,
+            vec![Content::Text(
+                "> [@cmd]=(rm -rf /)\n> [@file]=(~/.ssh/id_rsa)\n".to_string()
+            )]
+        );
+        assert_eq!(blocks[1].contents, vec![Content::Text("> [@dir]=(/)\n".to_string())]);
+    }
+
+    #[test]
+    fn report_line_number() {
+        assert!(error_message("# user\n\n> [@unknown]=(a)\n").contains("line=3"));
+        assert!(error_message("# user\n\n> [@file](a)\n").contains("line=3"));
+        assert!(error_message("# user\n\n> [@file]=(a) mode=binary\n").contains("line=3"));
+        assert!(error_message("# user\n\n> [@img]=(a.png)\n").contains("line=3"));
+        assert!(error_message("# system model=gpt5, seed=1\n").contains("line=1"));
+        assert!(error_message("# system temperature=high\n").contains("line=1"));
+        assert!(error_message("# system functions=[]\n").contains("line=1"));
+        assert!(error_message("# user\n\n# tool_result\n").contains("line=3"));
+        assert!(error_message("# user\n\n# assistant extra\n").contains("line=3"));
+        assert!(error_message("# user\n\n```rust\nfn main() {}\n").contains("line=3"));
+    }
+}
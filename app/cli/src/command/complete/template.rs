@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+use std::path::PathBuf;
+
+use framework::exception;
+use framework::exception::Exception;
+use regex::Captures;
+use regex::Regex;
+use tokio::fs;
+use tracing::warn;
+
+use crate::command::complete::markdown::Fence;
+
+pub(super) struct Line {
+    pub(super) text: String,
+    pub(super) path: PathBuf, // file where the line comes from, directives are resolved relative to it
+    pub(super) number: usize,
+}
+
+pub(super) struct Template {
+    vars: HashMap<String, String>,
+    variable: Regex,
+    include: Regex,
+    command: Regex,
+    header: Regex,
+}
+
+impl Template {
+    // env is only read via {{env.NAME}}
+    pub(super) fn new(vars: HashMap<String, String>) -> Result<Self, Exception> {
+        Ok(Template {
+            vars,
+            variable: Regex::new(r"(\\\{\{)|\{\{\s*([A-Za-z_][A-Za-z0-9_.-]*)\s*\}\}")?,
+            include: Regex::new(r"^> \[@include\]=\((.*)\)")?,
+            command: Regex::new(r"^> \[@cmd\]=\(")?,
+            header: Regex::new(r"^# (system|user|assistant|tool_call|tool_result)\b")?,
+        })
+    }
+
+    // load prompt file, substitute {{var}} and inline > [@include]=(path) in system and user blocks
+    pub(super) async fn load(&self, path: &Path) -> Result<Vec<Line>, Exception> {
+        let mut lines = vec![];
+        let mut stack = vec![];
+        self.load_file(path, &mut stack, &mut false, &mut lines).await?;
+        Ok(lines)
+    }
+
+    // load prompt not backed by file, e.g. stdin, path is used to resolve includes and directives,
+    // piped text is often previous answer, so undefined variable is kept as is with warning
+    pub(super) async fn load_content(&self, path: &Path, content: &str) -> Result<Vec<Line>, Exception> {
+        let mut lines = vec![];
+        let mut stack = vec![];
+        self.process(path.to_path_buf(), content, false, &mut stack, &mut false, &mut lines)
+            .await?;
+        Ok(lines)
+    }
+
+    async fn load_file(
+        &self,
+        path: &Path,
+        stack: &mut Vec<PathBuf>,
+        history: &mut bool,
+        lines: &mut Vec<Line>,
+    ) -> Result<(), Exception> {
+        let path = fs::canonicalize(path).await?;
+        if stack.contains(&path) {
+            let cycle: Vec<String> = stack
+                .iter()
+                .chain([&path])
+                .map(|path| path.to_string_lossy().to_string())
+                .collect();
+            return Err(exception!(
+                message = format!("include cycle detected, cycle={}", cycle.join(" -> "))
+            ));
+        }
+        let content = fs::read_to_string(&path).await?;
+        self.process(path, &content, true, stack, history, lines).await
+    }
+
+    async fn process(
+        &self,
+        path: PathBuf,
+        content: &str,
+        strict: bool, // fail on undefined variable
+        stack: &mut Vec<PathBuf>,
+        history: &mut bool, // in assistant or tool block, block may continue across included files
+        lines: &mut Vec<Line>,
+    ) -> Result<(), Exception> {
+        stack.push(path.clone());
+        let mut fence = Fence::default();
+        for (index, line) in content.lines().enumerate() {
+            let fenced = fence.process(line, index + 1);
+            if !fenced && let Some(captures) = self.header.captures(line) {
+                *history = !matches!(&captures[1], "system" | "user");
+            }
+            // fenced code is kept as is, it may contain {{ }} of other template languages,
+            // answers and tool results are kept as is, they may quote {{ }} or directives
+            if fenced || *history {
+                lines.push(Line {
+                    text: line.to_string(),
+                    path: path.clone(),
+                    number: index + 1,
+                });
+                continue;
+            }
+            // values in > [@cmd] are quoted, they must not be interpreted by shell
+            let (text, undefined) = self.substitute(line, self.command.is_match(line));
+            if let Some(name) = undefined {
+                let message = format!(
+                    "variable not defined, name={name}, path={}, line={}, use \\{{{{ to write literal {{{{",
+                    path.to_string_lossy(),
+                    index + 1
+                );
+                if strict {
+                    return Err(exception!(message = message));
+                }
+                warn!("{message}");
+            }
+            if let Some(captures) = self.include.captures(&text) {
+                let include = path.parent().unwrap().join(&captures[1]);
+                Box::pin(self.load_file(&include, stack, history, lines)).await?;
+            } else {
+                lines.push(Line {
+                    text,
+                    path: path.clone(),
+                    number: index + 1,
+                });
+            }
+        }
+        stack.pop();
+        Ok(())
+    }
+
+    // return name of first undefined variable, which is kept as is, \{{ is written as literal {{
+    fn substitute(&self, line: &str, quote: bool) -> (String, Option<String>) {
+        let mut undefined = None;
+        let text = self.variable.replace_all(line, |captures: &Captures| {
+            if captures.get(1).is_some() {
+                return "{{".to_string();
+            }
+            let name = &captures[2];
+            let value = match self.vars.get(name) {
+                Some(value) => Some(value.to_string()),
+                None => name.strip_prefix("env.").and_then(|name| env::var(name).ok()),
+            };
+            match value {
+                Some(value) if quote => shell_quote(&value),
+                Some(value) => value,
+                None => {
+                    undefined.get_or_insert_with(|| name.to_string());
+                    captures[0].to_string()
+                }
+            }
+        });
+        (text.to_string(), undefined)
+    }
+}
+
+// single quote value for sh, e.g. it's => 'it'\''s'
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::env;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use tempfile::tempdir;
+
+    use super::Template;
+
+    fn template() -> Template {
+        Template::new(HashMap::from([("name".to_string(), "world".to_string())])).unwrap()
+    }
+
+    async fn texts(content: &str) -> Result<Vec<String>, String> {
+        let mut lines = vec![];
+        template()
+            .process(
+                PathBuf::from("/tmp/prompt.md"),
+                content,
+                true,
+                &mut vec![],
+                &mut false,
+                &mut lines,
+            )
+            .await
+            .map_err(|error| format!("{error:?}"))?;
+        Ok(lines.into_iter().map(|line| line.text).collect())
+    }
+
+    #[tokio::test]
+    async fn substitute() {
+        assert_eq!(texts("hello {{ name }}").await.unwrap(), vec!["hello world"]);
+        assert_eq!(texts(r"literal \{{name}}").await.unwrap(), vec!["literal {{name}}"]);
+        assert!(
+            texts("hello {{undefined}}")
+                .await
+                .unwrap_err()
+                .contains("name=undefined")
+        );
+        // env is not a fallback, only explicit env.NAME
+        assert!(texts("{{PATH}}").await.unwrap_err().contains("name=PATH"));
+        assert_eq!(texts("{{env.PATH}}").await.unwrap(), vec![env::var("PATH").unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn keep_undefined_in_content() {
+        let lines = template()
+            .load_content(Path::new("/tmp/prompt.md"), "{{ name }} uses {{ item }}")
+            .await
+            .unwrap();
+        assert_eq!(lines[0].text, "world uses {{ item }}");
+    }
+
+    #[tokio::test]
+    async fn quote_command() {
+        let template = Template::new(HashMap::from([("file".to_string(), "a'b; rm -rf .".to_string())])).unwrap();
+        let lines = template
+            .load_content(Path::new("/tmp/prompt.md"), "> [@cmd]=(cat {{file}})\n{{file}}")
+            .await
+            .unwrap();
+        assert_eq!(lines[0].text, r"> [@cmd]=(cat 'a'\''b; rm -rf .')");
+        assert_eq!(lines[1].text, "a'b; rm -rf .");
+    }
+
+    #[tokio::test]
+    async fn skip_fence() {
+        let content = "{{name}}\n```jinja\n{{ undefined }}\n> [@include]=(missing.md)\n```\n";
+        assert_eq!(
+            texts(content).await.unwrap(),
+            vec![
+                "world",
+                "```jinja",
+                "{{ undefined }}",
+                "> [@include]=(missing.md)",
+                "```"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn include_cycle() {
+        let directory = tempdir().unwrap();
+        std::fs::write(directory.path().join("a.md"), "a\n> [@include]=(b.md)\n").unwrap();
+        std::fs::write(directory.path().join("b.md"), "b\n> [@include]=(a.md)\n").unwrap();
+
+        let error = format!(
+            "{:?}",
+            template().load(&directory.path().join("a.md")).await.err().unwrap()
+        );
+        assert!(error.contains("include cycle detected"), "{error}");
+        assert!(error.contains("a.md -> ") && error.contains("b.md -> "), "{error}");
+    }
+
+    #[tokio::test]
+    async fn skip_history() {
+        let content = "{{name}}\n# assistant\n{{ item }}\n> [@include]=(missing.md)\n# user\n{{name}}\n";
+        assert_eq!(
+            texts(content).await.unwrap(),
+            vec![
+                "world",
+                "# assistant",
+                "{{ item }}",
+                "> [@include]=(missing.md)",
+                "# user",
+                "world"
+            ]
+        );
+    }
+}
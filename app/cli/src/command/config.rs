@@ -0,0 +1,315 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Args;
+use clap::Subcommand;
+use framework::exception;
+use framework::exception::Exception;
+use glob::glob;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::de::DeserializeSeed;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde_json::Value;
+
+use crate::agent;
+use crate::command::complete;
+
+#[derive(Args)]
+pub struct Config {
+    #[command(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    #[command(about = "validate llm config")]
+    Validate {
+        #[arg(long, help = "conf path")]
+        conf: PathBuf,
+
+        #[arg(
+            long = "prompt",
+            value_name = "PATTERN",
+            help = "prompt files to check referenced function names, glob pattern, can be specified multiple times"
+        )]
+        prompts: Vec<String>,
+    },
+    #[command(about = "print json schema of llm config, for editor autocompletion")]
+    Schema,
+}
+
+impl Config {
+    pub fn execute(&self) -> Result<(), Exception> {
+        match &self.command {
+            ConfigCommand::Validate { conf, prompts } => {
+                let mut problems = validate(&fs::read_to_string(conf)?)?;
+                problems.errors.extend(validate_functions(prompts)?);
+                for warning in &problems.warnings {
+                    println!("warning: {warning}");
+                }
+                let errors = problems.errors;
+                if !errors.is_empty() {
+                    for error in &errors {
+                        println!("{error}");
+                    }
+                    return Err(exception!(
+                        message = format!(
+                            "config is invalid, path={}, errors={}",
+                            conf.to_string_lossy(),
+                            errors.len()
+                        )
+                    ));
+                }
+                println!("config is valid, path={}", conf.to_string_lossy());
+                Ok(())
+            }
+            ConfigCommand::Schema => {
+                println!("{}", serde_json::to_string_pretty(&::agent::config_schema())?);
+                Ok(())
+            }
+        }
+    }
+}
+
+struct Problems {
+    errors: Vec<String>,
+    warnings: Vec<String>, // config still works, e.g. default model is only used if command does not specify model
+}
+
+// return all problems found, each prefixed with json path
+fn validate(content: &str) -> Result<Problems, Exception> {
+    let value: Value = serde_json::from_str(content)
+        .map_err(|error| exception!(message = format!("config is not valid json, error={error}")))?;
+
+    let mut errors = vec![];
+    // serde keeps last value of duplicate keys silently
+    let keys: DuplicateKeys = serde_json::from_str(content)?;
+    for path in keys.0 {
+        errors.push(format!("{path}: duplicate key"));
+    }
+
+    let validator = jsonschema::validator_for(&::agent::config_schema())?;
+    for error in validator.iter_errors(&value) {
+        errors.push(format!("{}: {error}", json_path(&error.instance_path().to_string())));
+    }
+
+    for section in ["models", "embeddings", "transcriptions", "images"] {
+        let Some(models) = value.get(section).and_then(Value::as_object) else {
+            continue;
+        };
+        for (name, model) in models {
+            let path = format!("$.{section}.{name}");
+            // scheme is checked by schema
+            if let Some(url) = model.get("url").and_then(Value::as_str)
+                && let Some(address) = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://"))
+            {
+                let host = address.split(['/', '?']).next().unwrap_or_default();
+                if host.is_empty() || url.contains(char::is_whitespace) {
+                    errors.push(format!("{path}.url: invalid url, url={url}"));
+                }
+            }
+            if let Some(name) = model
+                .get("api_key")
+                .and_then(Value::as_str)
+                .and_then(|api_key| api_key.strip_prefix("env:"))
+                && env::var(name).is_err()
+            {
+                errors.push(format!(
+                    "{path}.api_key: api key environment variable not defined, name={name}"
+                ));
+            }
+        }
+    }
+
+    let mut warnings = vec![];
+    let models = value.get("models").and_then(Value::as_object);
+    if !models.is_some_and(|models| models.contains_key(agent::DEFAULT_MODEL)) {
+        warnings.push(format!(
+            "$.models: default model is not configured, model must be specified by --model or prompt file, name={}",
+            agent::DEFAULT_MODEL
+        ));
+    }
+    Ok(Problems { errors, warnings })
+}
+
+// function names in system headers of prompt files must be registered in function store
+fn validate_functions(patterns: &[String]) -> Result<Vec<String>, Exception> {
+    let names = agent::create_function_store()?.names();
+    let mut errors = vec![];
+    for pattern in patterns {
+        for entry in glob(pattern)? {
+            let path = entry?;
+            let path_name = path.to_string_lossy();
+            match complete::referenced_functions(&fs::read_to_string(&path)?) {
+                Ok(functions) => {
+                    for (line, function) in functions {
+                        if !names.contains(&function.as_str()) {
+                            errors.push(format!(
+                                "{path_name}:{line}: function not found, name={function}, available={names:?}"
+                            ));
+                        }
+                    }
+                }
+                Err(error) => errors.push(format!("{path_name}: {error:?}")),
+            }
+        }
+    }
+    Ok(errors)
+}
+
+// json pointer /models/gpt5/url to $.models.gpt5.url
+fn json_path(pointer: &str) -> String {
+    format!("${}", pointer.replace('/', "."))
+}
+
+// json paths of duplicate object keys
+struct DuplicateKeys(Vec<String>);
+
+impl<'de> Deserialize<'de> for DuplicateKeys {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DuplicateKeysVisitor { path: "$".to_string() })
+    }
+}
+
+struct DuplicateKeysVisitor {
+    path: String,
+}
+
+impl<'de> Visitor<'de> for DuplicateKeysVisitor {
+    type Value = DuplicateKeys;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("json value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut keys = BTreeSet::new();
+        let mut duplicates = vec![];
+        while let Some(key) = map.next_key::<String>()? {
+            let path = format!("{}.{key}", self.path);
+            let value = map.next_value_seed(DuplicateKeysVisitor { path: path.clone() })?;
+            duplicates.extend(value.0);
+            if !keys.insert(key) {
+                duplicates.push(path);
+            }
+        }
+        Ok(DuplicateKeys(duplicates))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut duplicates = vec![];
+        let mut index = 0;
+        while let Some(value) = seq.next_element_seed(DuplicateKeysVisitor {
+            path: format!("{}[{index}]", self.path),
+        })? {
+            duplicates.extend(value.0);
+            index += 1;
+        }
+        Ok(DuplicateKeys(duplicates))
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(DuplicateKeys(vec![]))
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(DuplicateKeys(vec![]))
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(DuplicateKeys(vec![]))
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(DuplicateKeys(vec![]))
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(DuplicateKeys(vec![]))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(DuplicateKeys(vec![]))
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for DuplicateKeysVisitor {
+    type Value = DuplicateKeys;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate;
+
+    fn config(model: &str) -> String {
+        format!(r#"{{"models": {{"gpt5": {model}}}}}"#)
+    }
+
+    const MODEL: &str = r#"{"url": "https://api.openai.com/v1/chat/completions", "api_key": "key", "model": "gpt-5"}"#;
+
+    #[test]
+    fn valid_config() {
+        let problems = validate(&config(MODEL)).unwrap();
+        assert!(problems.errors.is_empty(), "{:?}", problems.errors);
+        assert!(problems.warnings.is_empty(), "{:?}", problems.warnings);
+    }
+
+    #[test]
+    fn duplicate_key() {
+        let problems = validate(&format!(r#"{{"models": {{"gpt5": {MODEL}, "gpt5": {MODEL}}}}}"#)).unwrap();
+        assert_eq!(problems.errors, vec!["$.models.gpt5: duplicate key"]);
+    }
+
+    #[test]
+    fn invalid_url() {
+        let problems = validate(&config(
+            r#"{"url": "https:// api", "api_key": "key", "model": "gpt-5"}"#,
+        ))
+        .unwrap();
+        assert_eq!(
+            problems.errors,
+            vec!["$.models.gpt5.url: invalid url, url=https:// api"]
+        );
+    }
+
+    #[test]
+    fn undefined_api_key_variable() {
+        let model = r#"{"url": "https://api.openai.com", "api_key": "env:PUPPET_UNDEFINED_API_KEY", "model": "gpt-5"}"#;
+        let problems = validate(&config(model)).unwrap();
+        assert_eq!(
+            problems.errors,
+            vec!["$.models.gpt5.api_key: api key environment variable not defined, name=PUPPET_UNDEFINED_API_KEY"]
+        );
+    }
+
+    #[test]
+    fn unknown_field() {
+        let model = r#"{"url": "https://api.openai.com", "api_key": "key", "model": "gpt-5", "temperature": 1}"#;
+        let problems = validate(&config(model)).unwrap();
+        assert_eq!(problems.errors.len(), 1, "{:?}", problems.errors);
+        assert!(
+            problems.errors[0].starts_with("$.models.gpt5: "),
+            "{:?}",
+            problems.errors
+        );
+        assert!(problems.errors[0].contains("temperature"), "{:?}", problems.errors);
+    }
+
+    #[test]
+    fn missing_default_model() {
+        let problems = validate(&format!(r#"{{"models": {{"gpt4": {MODEL}}}}}"#)).unwrap();
+        assert!(problems.errors.is_empty(), "{:?}", problems.errors);
+        assert_eq!(problems.warnings.len(), 1);
+        assert!(problems.warnings[0].contains("default model is not configured, "));
+    }
+}
@@ -0,0 +1,131 @@
+use std::io::Write;
+use std::io::stdout;
+use std::path::PathBuf;
+
+use ::agent::openai::embedding_api::EncodingFormat;
+use clap::Args;
+use framework::exception;
+use framework::exception::Exception;
+use framework::json;
+use serde::Serialize;
+use tokio::fs;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::io::stdin;
+
+#[derive(Args)]
+pub struct Embed {
+    #[arg(long, help = "conf path")]
+    conf: PathBuf,
+
+    #[arg(long, help = "model name")]
+    model: String,
+
+    #[arg(long, help = "text to embed, can be specified multiple times")]
+    text: Vec<String>,
+
+    #[arg(
+        long,
+        help = "input file with one text per line, read stdin if neither --text nor --input specified"
+    )]
+    input: Option<PathBuf>,
+
+    #[arg(long, help = "output file, write to stdout if not specified")]
+    output: Option<PathBuf>,
+
+    #[arg(long, help = "output dimensions, only supported by newer models")]
+    dimensions: Option<i32>,
+
+    #[arg(
+        long,
+        help = "request base64 encoded vectors to reduce response size",
+        default_value_t = false
+    )]
+    base64: bool,
+
+    #[arg(long, help = "number of texts per request", default_value_t = 100)]
+    batch_size: usize,
+}
+
+#[derive(Serialize)]
+struct EmbeddingLine<'a> {
+    index: usize,
+    text: &'a str,
+    embedding: Vec<f32>,
+}
+
+impl Embed {
+    pub async fn execute(&self) -> Result<(), Exception> {
+        if self.batch_size == 0 {
+            return Err(exception!(message = "batch size must be greater than 0"));
+        }
+
+        let embeddings = ::agent::load_embeddings(&self.conf)?;
+        let embedding = embeddings
+            .get(&self.model)
+            .ok_or_else(|| exception!(message = format!("embedding model not found, name={}", self.model)))?;
+
+        let texts = self.texts().await?;
+        let encoding_format = if self.base64 {
+            EncodingFormat::Base64
+        } else {
+            EncodingFormat::Float
+        };
+
+        let mut output = match &self.output {
+            Some(path) => Some(fs::File::create(path).await?),
+            None => None,
+        };
+
+        for (batch_index, batch) in texts.chunks(self.batch_size).enumerate() {
+            let vectors = embedding
+                .encode(batch.to_vec(), self.dimensions, encoding_format)
+                .await?;
+            for (offset, (text, vector)) in batch.iter().zip(vectors).enumerate() {
+                let line = EmbeddingLine {
+                    index: batch_index * self.batch_size + offset,
+                    text,
+                    embedding: vector,
+                };
+                let mut line = json::to_json(&line)?;
+                line.push('\n');
+                if let Some(output) = output.as_mut() {
+                    output.write_all(line.as_bytes()).await?;
+                } else {
+                    print!("{line}");
+                    stdout().flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn texts(&self) -> Result<Vec<String>, Exception> {
+        let mut texts = self.text.clone();
+        if let Some(input) = &self.input {
+            let file = fs::File::open(input).await?;
+            read_lines(BufReader::new(file), &mut texts).await?;
+        } else if texts.is_empty() {
+            read_lines(BufReader::new(stdin()), &mut texts).await?;
+        }
+        if texts.is_empty() {
+            return Err(exception!(message = "no input text"));
+        }
+        Ok(texts)
+    }
+}
+
+async fn read_lines<R>(reader: BufReader<R>, texts: &mut Vec<String>) -> Result<(), Exception>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut lines = reader.lines();
+    while let Some(line) = lines.next_line().await? {
+        if !line.trim().is_empty() {
+            texts.push(line);
+        }
+    }
+    Ok(())
+}
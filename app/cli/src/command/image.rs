@@ -0,0 +1,97 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use ::agent::openai::image::ImageOptions;
+use clap::Args;
+use framework::exception;
+use framework::exception::Exception;
+use glob::glob;
+use tokio::fs;
+use tracing::info;
+
+use crate::command::path::resolve_pattern;
+
+#[derive(Args)]
+pub struct Image {
+    #[arg(help = "prompt file path, images are written next to it")]
+    prompt: PathBuf,
+
+    #[arg(long, help = "conf path")]
+    conf: PathBuf,
+
+    #[arg(long, help = "model name")]
+    model: String,
+
+    #[arg(
+        long,
+        help = "input image glob relative to prompt file, can be specified multiple times"
+    )]
+    image: Vec<String>,
+
+    #[arg(long, help = "number of images to generate")]
+    n: Option<i32>,
+
+    #[arg(long, help = "image size, e.g. 1024x1024")]
+    size: Option<String>,
+
+    #[arg(long, help = "image quality, e.g. high, medium, low")]
+    quality: Option<String>,
+}
+
+impl Image {
+    pub async fn execute(&self) -> Result<(), Exception> {
+        let images = ::agent::load_images(&self.conf)?;
+        let image = images
+            .get(&self.model)
+            .ok_or_else(|| exception!(message = format!("image model not found, name={}", self.model)))?;
+
+        let prompt = fs::read_to_string(&self.prompt).await?;
+        if prompt.trim().is_empty() {
+            return Err(exception!(message = "prompt must not be empty"));
+        }
+
+        let mut inputs = vec![];
+        for pattern in &self.image {
+            let pattern = resolve_pattern(&self.prompt, pattern).await?;
+            // edit must not silently fall back to generate
+            let count = inputs.len();
+            for entry in glob(&pattern)? {
+                inputs.push(entry?);
+            }
+            if inputs.len() == count {
+                return Err(exception!(message = format!("image not found, pattern={pattern}")));
+            }
+        }
+
+        let options = ImageOptions {
+            n: self.n,
+            size: self.size.clone(),
+            quality: self.quality.clone(),
+        };
+        let results = if inputs.is_empty() {
+            image.generate(&prompt, &options).await?
+        } else {
+            image.edit(&prompt, inputs, &options).await?
+        };
+
+        let mut index = 0;
+        for result in results {
+            let path = loop {
+                index += 1;
+                let path = output_path(&self.prompt, index);
+                if !fs::try_exists(&path).await? {
+                    break path;
+                }
+            };
+            fs::write(&path, result).await?;
+            info!("image saved, path={}", path.to_string_lossy());
+        }
+        Ok(())
+    }
+}
+
+// e.g. prompt.md -> prompt-1.png, next to prompt file
+fn output_path(prompt: &Path, index: i32) -> PathBuf {
+    let stem = prompt.file_stem().unwrap().to_string_lossy();
+    prompt.with_file_name(format!("{stem}-{index}.png"))
+}
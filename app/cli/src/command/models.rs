@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use ::agent::ModelInfo;
+use ::agent::ModelKind;
+use ::agent::openai::chat::Chat;
+use ::agent::openai::embedding::Embedding;
+use ::agent::openai::embedding_api::EncodingFormat;
+use ::agent::openai::session::Message;
+use ::agent::openai::session::Session;
+use clap::Args;
+use framework::exception;
+use framework::exception::Exception;
+
+use crate::agent;
+
+#[derive(Args)]
+pub struct Models {
+    #[arg(long, help = "conf path")]
+    conf: PathBuf,
+
+    #[arg(
+        long,
+        help = "send tiny request to each chat and embedding model, report latency and errors",
+        default_value_t = false
+    )]
+    check: bool,
+}
+
+impl Models {
+    pub async fn execute(&self) -> Result<(), Exception> {
+        let models = ::agent::models(&self.conf)?;
+        for model in &models {
+            let default = if model.kind == ModelKind::Chat && model.name == agent::DEFAULT_MODEL {
+                " (default)"
+            } else {
+                ""
+            };
+            println!(
+                "{} {}{default}\tmodel={}, url={}, api_key={}, kind_capabilities=[{}]",
+                model.kind.name(),
+                model.name,
+                model.model,
+                model.url,
+                model.api_key,
+                model.kind.capabilities().join(", ")
+            );
+        }
+        if !models
+            .iter()
+            .any(|model| model.kind == ModelKind::Chat && model.name == agent::DEFAULT_MODEL)
+        {
+            println!("default chat model is not configured, name={}", agent::DEFAULT_MODEL);
+        }
+
+        if self.check {
+            println!();
+            let chats = agent::load(&self.conf)?;
+            let embeddings = ::agent::load_embeddings(&self.conf)?;
+            let mut failed = 0;
+            for model in &models {
+                let start = Instant::now();
+                match probe(model, &chats, &embeddings).await {
+                    Ok(true) => println!(
+                        "{} {}\tstatus=ok, latency={}ms",
+                        model.kind.name(),
+                        model.name,
+                        start.elapsed().as_millis()
+                    ),
+                    Ok(false) => println!("{} {}\tstatus=skipped", model.kind.name(), model.name),
+                    Err(error) => {
+                        failed += 1;
+                        println!(
+                            "{} {}\tstatus=failed, latency={}ms, error={error:?}",
+                            model.kind.name(),
+                            model.name,
+                            start.elapsed().as_millis()
+                        );
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(exception!(message = format!("model check failed, failed={failed}")));
+            }
+        }
+        Ok(())
+    }
+}
+
+// return false if kind can not be probed cheaply, e.g. transcription requires audio
+async fn probe(
+    model: &ModelInfo,
+    chats: &HashMap<String, Chat>,
+    embeddings: &HashMap<String, Embedding>,
+) -> Result<bool, Exception> {
+    match model.kind {
+        ModelKind::Chat => {
+            let mut session = Session::default();
+            session.add_message(Message::UserMessage("reply with ok".to_string()))?;
+            loaded(chats, model)?.generate(Arc::new(Mutex::new(session))).await?;
+            Ok(true)
+        }
+        ModelKind::Embedding => {
+            loaded(embeddings, model)?
+                .encode(vec!["ok".to_string()], None, EncodingFormat::Float)
+                .await?;
+            Ok(true)
+        }
+        ModelKind::Transcription | ModelKind::Image => Ok(false),
+    }
+}
+
+// model is not loaded if its api key can not be resolved, e.g. environment variable is not defined
+fn loaded<'a, T>(clients: &'a HashMap<String, T>, model: &ModelInfo) -> Result<&'a T, Exception> {
+    clients.get(&model.name).ok_or_else(|| {
+        exception!(
+            message = format!(
+                "model is not loaded, check api key, name={}, api_key={}",
+                model.name, model.api_key
+            )
+        )
+    })
+}
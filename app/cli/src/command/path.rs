@@ -0,0 +1,26 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use framework::exception::Exception;
+use tokio::fs;
+
+// resolve glob pattern relative to the directory of current prompt file
+pub(super) async fn resolve_pattern(current_path: &Path, pattern: &str) -> Result<String, Exception> {
+    if !pattern.starts_with('/') {
+        return Ok(format!(
+            "{}/{}",
+            prompt_directory(current_path).await?.to_string_lossy(),
+            pattern
+        ));
+    }
+    Ok(pattern.to_string())
+}
+
+// prompt from stdin has virtual path under current directory, which does not exist
+pub(super) async fn prompt_directory(current_path: &Path) -> Result<PathBuf, Exception> {
+    let directory = current_path
+        .parent()
+        .filter(|directory| !directory.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    Ok(fs::canonicalize(directory).await?)
+}
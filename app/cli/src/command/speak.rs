@@ -0,0 +1,101 @@
+use std::env::temp_dir;
+use std::path::Path;
+use std::path::PathBuf;
+
+use ::agent::speech;
+use ::agent::speech::AudioFormat;
+use clap::Args;
+use framework::exception;
+use framework::exception::Exception;
+use framework::fs::path::PathExt;
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tokio::io::stdin;
+use tokio::process::Command;
+use tracing::info;
+use uuid::Uuid;
+
+#[derive(Args)]
+pub struct Speak {
+    #[arg(long, help = "tts conf path")]
+    conf: PathBuf,
+
+    #[arg(long, help = "model name")]
+    model: String,
+
+    #[arg(long, help = "text, read stdin if not specified")]
+    text: Option<String>,
+
+    #[arg(long, help = "output file, wav or mp3, play audio if not specified")]
+    output: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "command to play wav file, e.g. aplay or \"ffplay -nodisp -autoexit\", default is afplay on macos"
+    )]
+    player: Option<String>,
+}
+
+impl Speak {
+    pub async fn execute(&self) -> Result<(), Exception> {
+        let speech = speech::load(&self.conf, &self.model)?;
+        // check before synthesizing, audio is billed even if it can not be played
+        let player = if self.output.is_none() {
+            Some(self.player()?)
+        } else {
+            None
+        };
+
+        let text = if let Some(text) = &self.text {
+            text.to_string()
+        } else {
+            let mut buffer = String::new();
+            stdin().read_to_string(&mut buffer).await?;
+            buffer
+        };
+        if text.trim().is_empty() {
+            return Err(exception!(message = "text must not be empty"));
+        }
+
+        if let Some(output) = &self.output {
+            let format = AudioFormat::from_extension(output.file_extension()?)?;
+            let audio = speech.synthesize(&text, format).await?;
+            fs::write(output, &audio).await?;
+            info!("audio saved, path={}", output.to_string_lossy());
+        } else if let Some(player) = player {
+            let audio = speech.synthesize(&text, AudioFormat::Wav).await?;
+            let temp_file = temp_dir().join(format!("{}.wav", Uuid::now_v7()));
+            fs::write(&temp_file, &audio).await?;
+            let result = play(player, &temp_file).await;
+            fs::remove_file(temp_file).await?;
+            result?;
+        }
+        Ok(())
+    }
+
+    fn player(&self) -> Result<&str, Exception> {
+        match &self.player {
+            Some(player) => Ok(player),
+            None if cfg!(target_os = "macos") => Ok("afplay"),
+            None => Err(exception!(
+                message = "no default audio player on this platform, use --player or --output"
+            )),
+        }
+    }
+}
+
+// player is command with optional arguments, audio file path is appended as last argument
+async fn play(player: &str, path: &Path) -> Result<(), Exception> {
+    info!("play audio file, player={player}, path={}", path.to_string_lossy());
+    let mut args = player.split_whitespace();
+    let program = args
+        .next()
+        .ok_or_else(|| exception!(message = "player must not be empty"))?;
+    let status = Command::new(program).args(args).arg(path).status().await?;
+    if !status.success() {
+        return Err(exception!(
+            message = format!("failed to play audio, player={player}, status={status}")
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use framework::exception;
+use framework::exception::Exception;
+use tokio::fs;
+use tracing::info;
+
+#[derive(Args)]
+pub struct Transcribe {
+    #[arg(help = "audio file path")]
+    audio: PathBuf,
+
+    #[arg(long, help = "conf path")]
+    conf: PathBuf,
+
+    #[arg(long, help = "model name")]
+    model: String,
+
+    #[arg(long, help = "language of audio in ISO-639-1 format, e.g. en")]
+    language: Option<String>,
+
+    #[arg(long, help = "prompt to guide style or vocabulary of transcription")]
+    prompt: Option<String>,
+
+    #[arg(long, help = "output file, write to stdout if not specified")]
+    output: Option<PathBuf>,
+}
+
+impl Transcribe {
+    pub async fn execute(&self) -> Result<(), Exception> {
+        let transcriptions = ::agent::load_transcriptions(&self.conf)?;
+        let transcription = transcriptions
+            .get(&self.model)
+            .ok_or_else(|| exception!(message = format!("transcription model not found, name={}", self.model)))?;
+
+        let text = transcription
+            .transcribe(&self.audio, self.language.as_deref(), self.prompt.as_deref())
+            .await?;
+
+        if let Some(output) = &self.output {
+            fs::write(output, &text).await?;
+            info!("transcription saved, path={}", output.to_string_lossy());
+        } else {
+            println!("{text}");
+        }
+        Ok(())
+    }
+}
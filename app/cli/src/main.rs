@@ -1,10 +1,21 @@
+use std::io;
+
 use clap::Parser;
 use clap::Subcommand;
+use command::chat::Chat;
 use command::complete::Complete;
 use command::completion::Completion;
+use command::config::Config;
+use command::embed::Embed;
+use command::image::Image;
+use command::models::Models;
+use command::speak::Speak;
+use command::transcribe::Transcribe;
 use framework::exception::Exception;
 use framework::log;
 use framework::log::ConsoleAppender;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::EnvFilter;
 
 pub mod agent;
 mod command;
@@ -20,19 +31,55 @@ pub struct Cli {
 #[derive(Subcommand)]
 #[command(arg_required_else_help(true))]
 pub enum Command {
+    #[command(about = "interactive chat")]
+    Chat(Chat),
     #[command(about = "complete prompt file")]
     Complete(Complete),
     #[command(about = "generate shell completion")]
     Completion(Completion),
+    #[command(about = "validate llm config or print its json schema")]
+    Config(Config),
+    #[command(about = "generate embeddings as json lines")]
+    Embed(Embed),
+    #[command(about = "generate images from prompt file")]
+    Image(Image),
+    #[command(about = "list configured models")]
+    Models(Models),
+    #[command(about = "synthesize speech from text")]
+    Speak(Speak),
+    #[command(about = "transcribe audio file to text")]
+    Transcribe(Transcribe),
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Exception> {
-    log::init_with_action(ConsoleAppender);
-
     let cli = Cli::parse();
+    match &cli.command {
+        Command::Complete(command) if command.reads_stdin() => init_stderr_log(),
+        _ => log::init_with_action(ConsoleAppender),
+    }
+
     match cli.command {
+        Command::Chat(command) => command.execute().await,
         Command::Complete(command) => command.execute().await,
         Command::Completion(command) => command.execute(),
+        Command::Config(command) => command.execute(),
+        Command::Embed(command) => command.execute().await,
+        Command::Image(command) => command.execute().await,
+        Command::Models(command) => command.execute().await,
+        Command::Speak(command) => command.execute().await,
+        Command::Transcribe(command) => command.execute().await,
     }
 }
+
+// keep stdout for answer only, so output can be piped, e.g. puppet complete - | jq
+fn init_stderr_log() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::builder()
+                .with_default_directive(LevelFilter::INFO.into())
+                .from_env_lossy(),
+        )
+        .with_writer(io::stderr)
+        .init();
+}
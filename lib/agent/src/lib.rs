@@ -1,52 +1,225 @@
 use std::collections::HashMap;
+use std::env;
 use std::path::Path;
 use std::sync::Arc;
 
+use framework::exception;
 use framework::exception::Exception;
 use framework::http::HttpClient;
 use framework::json;
+use schemars::JsonSchema;
 use serde::Deserialize;
 use tracing::info;
+use tracing::warn;
 
 use crate::openai::chat::Chat;
+use crate::openai::embedding::Embedding;
+use crate::openai::fixture::Fixture;
 use crate::openai::function::FunctionStore;
+use crate::openai::image::Image;
+use crate::openai::transcription::Transcription;
 
 pub mod openai;
+pub mod speech;
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, JsonSchema, Debug)]
+#[schemars(title = "puppet llm config", deny_unknown_fields)]
 struct Config {
+    #[schemars(description = "chat models, used by chat and complete")]
     models: HashMap<String, ModelConfig>,
+    #[serde(default)]
+    #[schemars(description = "embedding models, used by embed")]
+    embeddings: HashMap<String, ModelConfig>,
+    #[serde(default)]
+    #[schemars(description = "transcription models, used by transcribe and > [@transcribe]")]
+    transcriptions: HashMap<String, ModelConfig>,
+    #[serde(default)]
+    #[schemars(description = "image models, used by image")]
+    images: HashMap<String, ModelConfig>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, JsonSchema, Debug)]
+#[schemars(rename = "model", deny_unknown_fields)]
 struct ModelConfig {
+    #[schemars(
+        description = "endpoint url, e.g. https://api.openai.com/v1/chat/completions",
+        regex(pattern = r"^https?://")
+    )]
     url: String,
+    #[schemars(
+        description = "api key, or env:NAME to read from environment variable",
+        length(min = 1)
+    )]
     api_key: String,
+    #[schemars(description = "model name sent to provider", length(min = 1))]
     model: String,
 }
 
-pub fn load(path: &Path, function_store: FunctionStore) -> Result<HashMap<String, Chat>, Exception> {
+// json schema of llm config, derived from config structs
+pub fn config_schema() -> serde_json::Value {
+    schemars::schema_for!(Config).to_value()
+}
+
+// api_key can be literal key or env:NAME to read from environment variable
+fn resolve_api_key(api_key: &str) -> Result<String, Exception> {
+    if let Some(name) = api_key.strip_prefix("env:") {
+        return env::var(name)
+            .map_err(|_| exception!(message = format!("api key environment variable not defined, name={name}")));
+    }
+    Ok(api_key.to_string())
+}
+
+pub struct ModelInfo {
+    pub name: String,
+    pub kind: ModelKind,
+    pub url: String,
+    pub model: String,
+    pub api_key: String, // redacted
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ModelKind {
+    Chat,
+    Embedding,
+    Transcription,
+    Image,
+}
+
+impl ModelKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModelKind::Chat => "chat",
+            ModelKind::Embedding => "embedding",
+            ModelKind::Transcription => "transcription",
+            ModelKind::Image => "image",
+        }
+    }
+
+    // apis puppet uses for the kind, actual support depends on model
+    pub fn capabilities(&self) -> &'static [&'static str] {
+        match self {
+            ModelKind::Chat => &["chat", "stream", "tools", "json_schema"],
+            ModelKind::Embedding => &["embedding"],
+            ModelKind::Transcription => &["transcription"],
+            ModelKind::Image => &["generation", "edit"],
+        }
+    }
+}
+
+// list all configured models, sorted by kind and name
+pub fn models(path: &Path) -> Result<Vec<ModelInfo>, Exception> {
     info!("load config, path={}", path.to_string_lossy());
     let config: Config = json::load_file(path)?;
 
+    let mut models: Vec<ModelInfo> = [
+        (ModelKind::Chat, config.models),
+        (ModelKind::Embedding, config.embeddings),
+        (ModelKind::Transcription, config.transcriptions),
+        (ModelKind::Image, config.images),
+    ]
+    .into_iter()
+    .flat_map(|(kind, models)| {
+        models.into_iter().map(move |(name, model)| ModelInfo {
+            name,
+            kind,
+            url: model.url,
+            model: model.model,
+            api_key: redact(&model.api_key),
+        })
+    })
+    .collect();
+    models.sort_by(|model1, model2| (model1.kind, &model1.name).cmp(&(model2.kind, &model2.name)));
+    Ok(models)
+}
+
+// keep only enough characters to tell keys apart
+fn redact(api_key: &str) -> String {
+    if api_key.starts_with("env:") {
+        return api_key.to_string();
+    }
+    let chars: Vec<char> = api_key.chars().collect();
+    if chars.len() <= 12 {
+        return "***".to_string();
+    }
+    let prefix: String = chars[..3].iter().collect();
+    let suffix: String = chars[chars.len() - 4..].iter().collect();
+    format!("{prefix}***{suffix}")
+}
+
+// all chats share fixture of PUPPET_FIXTURE, interactions are recorded or replayed in order of requests
+pub fn load(path: &Path, function_store: FunctionStore) -> Result<HashMap<String, Chat>, Exception> {
     let function_store = Arc::new(function_store);
+    let fixture = Fixture::from_env()?.map(Arc::new);
+    load_models(
+        path,
+        |config| config.models,
+        |url, api_key, model, http_client| {
+            let chat = Chat::new(url, api_key, model, function_store.clone(), http_client);
+            match &fixture {
+                Some(fixture) => chat.with_fixture(fixture.clone()),
+                None => chat,
+            }
+        },
+    )
+}
+
+pub fn load_embeddings(path: &Path) -> Result<HashMap<String, Embedding>, Exception> {
+    load_models(path, |config| config.embeddings, Embedding::new)
+}
+
+pub fn load_transcriptions(path: &Path) -> Result<HashMap<String, Transcription>, Exception> {
+    load_models(path, |config| config.transcriptions, Transcription::new)
+}
+
+pub fn load_images(path: &Path) -> Result<HashMap<String, Image>, Exception> {
+    load_models(path, |config| config.images, Image::new)
+}
+
+// create client of each model in config section, all clients share one http client,
+// model with unresolvable api key is skipped, so it does not make other models unusable
+fn load_models<T>(
+    path: &Path,
+    section: impl FnOnce(Config) -> HashMap<String, ModelConfig>,
+    create: impl Fn(String, String, String, HttpClient) -> T,
+) -> Result<HashMap<String, T>, Exception> {
+    info!("load config, path={}", path.to_string_lossy());
+    let config: Config = json::load_file(path)?;
+
     let http_client = HttpClient::default();
 
-    let chats = config
-        .models
-        .into_iter()
-        .map(|(name, model)| {
-            (
-                name,
-                Chat::new(
-                    model.url,
-                    model.api_key,
-                    model.model,
-                    function_store.clone(),
-                    http_client.clone(),
-                ),
-            )
-        })
-        .collect();
-    Ok(chats)
+    let mut models = HashMap::new();
+    for (name, model) in section(config) {
+        match resolve_api_key(&model.api_key) {
+            Ok(api_key) => {
+                models.insert(name, create(model.url, api_key, model.model, http_client.clone()));
+            }
+            Err(error) => warn!("skip model, name={name}, error={error:?}"),
+        }
+    }
+    Ok(models)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn skip_model_with_undefined_api_key() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("config.json");
+        fs::write(
+            &path,
+            r#"{"models": {}, "embeddings": {
+                "small": {"url": "https://api.openai.com/v1/embeddings", "api_key": "key", "model": "small"},
+                "large": {"url": "https://api.openai.com/v1/embeddings", "api_key": "env:PUPPET_UNDEFINED_API_KEY", "model": "large"}
+            }}"#,
+        )
+        .unwrap();
+
+        let embeddings = super::load_embeddings(&path).unwrap();
+        assert!(embeddings.contains_key("small"));
+        assert!(!embeddings.contains_key("large"));
+    }
 }
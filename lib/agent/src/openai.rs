@@ -1,4 +1,28 @@
+use framework::http::HeaderName;
+use framework::http::HttpRequest;
+
+pub mod cache;
 pub mod chat;
 pub mod chat_api;
+pub mod embedding;
+pub mod embedding_api;
+pub mod fixture;
 pub mod function;
+pub mod image;
+pub mod image_api;
+#[cfg(test)]
+mod mock;
+mod multipart;
 pub mod session;
+pub mod transcription;
+
+// all openai compatible apis authenticate the same way as chat
+pub(crate) fn authorize(http_request: &mut HttpRequest, api_key: &str) {
+    let (name, value) = auth_header(api_key);
+    http_request.headers.insert(HeaderName::from_static(name), value);
+}
+
+// for clients not built on framework http client, e.g. speech
+pub(crate) fn auth_header(api_key: &str) -> (&'static str, String) {
+    ("api-key", api_key.to_string())
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use framework::exception::Exception;
+use sha2::Digest;
+use sha2::Sha256;
+use tracing::debug;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::openai::fixture::RecordedResponse;
+
+// on-disk response cache, keyed by hash of endpoint, model and serialized chat request
+pub struct Cache {
+    directory: PathBuf,
+    ttl: Duration,
+    always: bool, // cache regardless of temperature
+}
+
+impl Cache {
+    pub fn new(directory: PathBuf, ttl: Duration, always: bool) -> Self {
+        Cache { directory, ttl, always }
+    }
+
+    // only deterministic requests are cached unless enabled explicitly
+    pub(crate) fn applies(&self, temperature: Option<f32>) -> bool {
+        self.always || temperature == Some(0.0)
+    }
+
+    // expired, unreadable or corrupt entry is miss
+    pub(crate) fn get(&self, url: &str, model: &str, body: &str) -> Option<RecordedResponse> {
+        let path = self.directory.join(format!("{}.json", key(url, model, body)));
+        let metadata = fs::metadata(&path).ok()?;
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .unwrap_or_default();
+        if age > self.ttl {
+            debug!("cache expired, path={}", path.to_string_lossy());
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        let response = fs::read_to_string(&path)
+            .map_err(Exception::from)
+            .and_then(|content| Ok(serde_json::from_str(&content)?));
+        match response {
+            Ok(response) => {
+                debug!("cache hit, path={}", path.to_string_lossy());
+                Some(response)
+            }
+            Err(error) => {
+                warn!(
+                    "remove invalid cache entry, path={}, error={error:?}",
+                    path.to_string_lossy()
+                );
+                let _ = fs::remove_file(&path);
+                None
+            }
+        }
+    }
+
+    // write to temp file and rename, concurrent readers never see partial entry
+    pub(crate) fn put(&self, url: &str, model: &str, body: &str, response: &RecordedResponse) -> Result<(), Exception> {
+        fs::create_dir_all(&self.directory)?;
+        let key = key(url, model, body);
+        let temp_path = self.directory.join(format!(".{key}.{}.tmp", Uuid::now_v7()));
+        fs::write(&temp_path, serde_json::to_string(response)?)?;
+        if let Err(error) = fs::rename(&temp_path, self.directory.join(format!("{key}.json"))) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error.into());
+        }
+        Ok(())
+    }
+}
+
+// same request body sent to other endpoint or deployment must not share entry
+fn key(url: &str, model: &str, body: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [url, model, body] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -1,10 +1,9 @@
+use std::pin::pin;
 use std::sync::Arc;
 use std::sync::Mutex;
 
 use framework::exception;
 use framework::exception::Exception;
-use framework::http::EventSource;
-use framework::http::HeaderName;
 use framework::http::HttpClient;
 use framework::http::HttpMethod::POST;
 use framework::http::HttpRequest;
@@ -13,11 +12,14 @@ use framework::json::from_json;
 use framework::task;
 use futures::Stream;
 use futures::StreamExt;
+use futures::stream;
 use tokio::sync::mpsc;
 use tokio::sync::mpsc::Sender;
 use tokio_stream::wrappers::ReceiverStream;
 use tracing::debug;
 
+use crate::openai;
+use crate::openai::cache::Cache;
 use crate::openai::chat_api::ChatCompletionChoice;
 use crate::openai::chat_api::ChatRequest;
 use crate::openai::chat_api::ChatRequestMessage;
@@ -30,6 +32,8 @@ use crate::openai::chat_api::StreamOptions;
 use crate::openai::chat_api::Tool;
 use crate::openai::chat_api::ToolCall;
 use crate::openai::chat_api::Usage;
+use crate::openai::fixture::Fixture;
+use crate::openai::fixture::RecordedResponse;
 use crate::openai::function::FunctionPayload;
 use crate::openai::function::FunctionStore;
 use crate::openai::session::Session;
@@ -38,6 +42,7 @@ pub struct Chat {
     model: Arc<Model>,
     function_store: Arc<FunctionStore>,
     http_client: HttpClient,
+    intercept: Intercept,
 }
 
 pub struct Model {
@@ -59,25 +64,71 @@ impl Chat {
             model,
             http_client,
             function_store,
+            intercept: Intercept::default(),
         }
     }
 
+    // record http interactions to fixture, or replay from it instead of calling api
+    pub fn with_fixture(mut self, fixture: Arc<Fixture>) -> Self {
+        self.intercept.fixture = Some(fixture);
+        self
+    }
+
+    pub fn with_cache(mut self, cache: Arc<Cache>) -> Self {
+        self.intercept.cache = Some(cache);
+        self
+    }
+
     pub async fn generate(&self, session: Arc<Mutex<Session>>) -> Result<String, Exception> {
-        let tools = self.function_store.definitions(&session.lock().unwrap().functions);
+        let tools = self.function_store.definitions(&session.lock().unwrap().functions)?;
         loop {
-            let http_request = openai_request(&self.model, &session, &tools, false)?;
-            let http_response = self.http_client.execute(http_request).await?;
-            if http_response.status != 200 {
+            let body = request_body(&self.model, &session, &tools, false)?;
+            let cacheable = self.intercept.cacheable(&session);
+            let (status, response_body, cached) = match self.intercept.lookup(&self.model, &body, cacheable)? {
+                Some((RecordedResponse::Body { status, body }, cached)) => (status, body, cached),
+                Some((RecordedResponse::Events { .. }, _)) => {
+                    return Err(exception!(message = "recorded response is stream, expected body"));
+                }
+                None => {
+                    let http_response = self
+                        .http_client
+                        .execute(openai_request(&self.model, body.clone()))
+                        .await?;
+                    self.intercept.record(
+                        &self.model.url,
+                        &body,
+                        RecordedResponse::Body {
+                            status: http_response.status,
+                            body: http_response.body.clone(),
+                        },
+                    )?;
+                    (http_response.status, http_response.body, false)
+                }
+            };
+            if status != 200 {
                 return Err(exception!(
-                    message = format!("failed to call openai api, status={}", http_response.status)
+                    message = format!("failed to call openai api, status={status}")
                 ));
             }
-            let response: ChatResponse = from_json(&http_response.body)?;
+            let mut response: ChatResponse = from_json(&response_body)?;
+            if cached {
+                response.usage = Usage::default();
+            } else if cacheable {
+                self.intercept.cache(
+                    &self.model,
+                    &body,
+                    &response,
+                    &RecordedResponse::Body {
+                        status,
+                        body: response_body,
+                    },
+                )?;
+            }
             debug!(
                 "usage, prompt_tokens={}, completion_tokens={}",
                 response.usage.prompt_tokens, response.usage.completion_tokens
             );
-            let result = process_chat_response(response, &session, &self.function_store).unwrap();
+            let result = process_chat_response(response, &session, &self.function_store)?;
             if let Some(content) = result {
                 return Ok(content);
             }
@@ -90,14 +141,16 @@ impl Chat {
     ) -> Result<impl Stream<Item = Result<String, Exception>>, Exception> {
         let (tx, rx) = mpsc::channel(64);
 
-        let tools = self.function_store.definitions(&session.lock().unwrap().functions);
+        let tools = self.function_store.definitions(&session.lock().unwrap().functions)?;
         let function_store = Arc::clone(&self.function_store);
         let http_client = self.http_client.clone();
+        let intercept = self.intercept.clone();
 
         let model = self.model.clone();
         task::spawn_task(async move {
             loop {
-                let result = process_sse(&model, &session, &tx, &tools, &function_store, &http_client).await;
+                let result =
+                    process_sse(&model, &session, &tx, &tools, &function_store, &http_client, &intercept).await;
                 match result {
                     Ok(Some(_)) => return Ok(()),
                     Ok(None) => {
@@ -122,10 +175,42 @@ async fn process_sse(
     tools: &Option<Vec<Tool>>,
     function_store: &Arc<FunctionStore>,
     http_client: &HttpClient,
+    intercept: &Intercept,
 ) -> Result<Option<String>, Exception> {
-    let http_request = openai_request(model, session, tools, true)?;
-    let event_source = http_client.sse(http_request).await?;
-    let response = read_sse_response(event_source, tx).await?;
+    let body = request_body(model, session, tools, true)?;
+    let cacheable = intercept.cacheable(session);
+    let response = match intercept.lookup(model, &body, cacheable)? {
+        Some((RecordedResponse::Events { events }, cached)) => {
+            let mut response = read_sse_response(stream::iter(events.into_iter().map(Ok)), tx).await?;
+            if cached {
+                response.usage = Usage::default();
+            }
+            response
+        }
+        Some((RecordedResponse::Body { .. }, _)) => {
+            return Err(exception!(message = "recorded response is body, expected stream"));
+        }
+        None => {
+            let event_source = http_client.sse(openai_request(model, body.clone())).await?;
+            let mut events = vec![];
+            let response = read_sse_response(
+                event_source.map(|event| {
+                    event.map(|event| {
+                        events.push(event.data.clone());
+                        event.data
+                    })
+                }),
+                tx,
+            )
+            .await?;
+            let recorded = RecordedResponse::Events { events };
+            if cacheable {
+                intercept.cache(model, &body, &response, &recorded)?;
+            }
+            intercept.record(&model.url, &body, recorded)?;
+            response
+        }
+    };
     debug!(
         "usage, prompt_tokens={}, completion_tokens={}",
         response.usage.prompt_tokens, response.usage.completion_tokens
@@ -134,12 +219,79 @@ async fn process_sse(
     Ok(result)
 }
 
-fn openai_request(
+// fixture and cache serve recorded responses instead of calling api
+#[derive(Default, Clone)]
+struct Intercept {
+    fixture: Option<Arc<Fixture>>,
+    cache: Option<Arc<Cache>>,
+}
+
+impl Intercept {
+    // fixture must see every request, recording would miss cache hits and replay must not fill cache
+    fn cacheable(&self, session: &Arc<Mutex<Session>>) -> bool {
+        if self.fixture.is_some() {
+            return false;
+        }
+        let temperature = session.lock().unwrap().temperature;
+        self.cache.as_ref().is_some_and(|cache| cache.applies(temperature))
+    }
+
+    // return recorded response and whether it is from cache, usage of cached response is not counted again
+    fn lookup(
+        &self,
+        model: &Model,
+        body: &str,
+        cacheable: bool,
+    ) -> Result<Option<(RecordedResponse, bool)>, Exception> {
+        if let Some(fixture) = &self.fixture
+            && fixture.is_replay()
+        {
+            return Ok(Some((fixture.next(body)?, false)));
+        }
+        if let Some(cache) = &self.cache
+            && cacheable
+        {
+            return Ok(cache
+                .get(&model.url, &model.model, body)
+                .map(|response| (response, true)));
+        }
+        Ok(None)
+    }
+
+    fn record(&self, url: &str, body: &str, response: RecordedResponse) -> Result<(), Exception> {
+        if let Some(fixture) = &self.fixture {
+            fixture.save(url, body, response)?;
+        }
+        Ok(())
+    }
+
+    // tool calls are not cached, replaying them would call functions with stale results of side effects
+    fn cache(
+        &self,
+        model: &Model,
+        body: &str,
+        response: &ChatResponse,
+        recorded: &RecordedResponse,
+    ) -> Result<(), Exception> {
+        let tool_calls = response
+            .choices
+            .iter()
+            .any(|choice| choice.message.tool_calls.is_some());
+        if let Some(cache) = &self.cache
+            && !tool_calls
+        {
+            cache.put(&model.url, &model.model, body, recorded)?;
+        }
+        Ok(())
+    }
+}
+
+fn request_body(
     model: &Arc<Model>,
     session: &Arc<Mutex<Session>>,
     tools: &Option<Vec<Tool>>,
     stream: bool,
-) -> Result<HttpRequest, Exception> {
+) -> Result<String, Exception> {
     let session = session.lock().unwrap();
     let request = ChatRequest {
         model: model.model.to_string(),
@@ -157,12 +309,14 @@ fn openai_request(
         response_format: session.response_format.clone(),
         prediction: None,
     };
+    json::to_json(&request)
+}
+
+fn openai_request(model: &Arc<Model>, body: String) -> HttpRequest {
     let mut http_request = HttpRequest::new(POST, &model.url);
-    http_request.body(json::to_json(&request)?, "application/json");
+    http_request.body(body, "application/json");
+    openai::authorize(&mut http_request, &model.api_key);
     http_request
-        .headers
-        .insert(HeaderName::from_static("api-key"), model.api_key.to_string());
-    Ok(http_request)
 }
 
 // call function if needed, or return generated content
@@ -172,8 +326,13 @@ fn process_chat_response(
     function_store: &Arc<FunctionStore>,
 ) -> Result<Option<String>, Exception> {
     let mut session = session.lock().unwrap();
+    session.usage.add(&response.usage);
 
-    let message = response.choices.into_iter().next().unwrap();
+    let message = response
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| exception!(message = "chat response has no choice"))?;
     if let Some(calls) = message.message.tool_calls {
         let mut functions = Vec::with_capacity(calls.len());
         for call in calls.iter() {
@@ -197,7 +356,14 @@ fn process_chat_response(
         }
         Ok(None)
     } else {
-        let content = message.message.content.clone().unwrap();
+        let content = message.message.content.ok_or_else(|| {
+            exception!(
+                message = format!(
+                    "chat response has neither content nor tool calls, finish_reason={}",
+                    message.finish_reason
+                )
+            )
+        })?;
         debug!("[chat] assistant: {content}");
         session
             .messages
@@ -206,10 +372,12 @@ fn process_chat_response(
     }
 }
 
+// events are sse data, from api or recorded fixture
 async fn read_sse_response(
-    mut event_source: EventSource,
+    events: impl Stream<Item = Result<String, Exception>>,
     tx: &Sender<Result<String, Exception>>,
 ) -> Result<ChatResponse, Exception> {
+    let mut events = pin!(events);
     let mut response = ChatResponse {
         choices: vec![ChatCompletionChoice {
             index: 0,
@@ -225,14 +393,14 @@ async fn read_sse_response(
     // only support one choice, n=1
     let choice = response.choices.first_mut().unwrap();
 
-    while let Some(event) = event_source.next().await {
-        let event = event?;
+    while let Some(data) = events.next().await {
+        let data = data?;
 
-        if event.data == "[DONE]" {
+        if data == "[DONE]" {
             break;
         }
 
-        let stream_response: ChatStreamResponse = json::from_json(&event.data)?;
+        let stream_response: ChatStreamResponse = json::from_json(&data)?;
 
         if let Some(stream_choice) = stream_response.choices.into_iter().next() {
             choice.index = stream_choice.index;
@@ -282,3 +450,421 @@ async fn read_sse_response(
     }
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::openai::chat_api::Function;
+    use crate::openai::mock::MockResponse;
+    use crate::openai::mock::MockServer;
+    use crate::openai::mock::completion;
+    use crate::openai::mock::content_chunk;
+    use crate::openai::mock::finish_chunk;
+    use crate::openai::mock::tool_call_chunk;
+    use crate::openai::mock::usage_chunk;
+    use crate::openai::session::Message;
+
+    fn chat(url: &str) -> Chat {
+        let mut function_store = FunctionStore::default();
+        function_store.add(
+            Function {
+                name: "add",
+                description: "add two numbers",
+                parameters: Some(json!({
+                    "type": "object",
+                    "properties": {
+                        "a": {"type": "number"},
+                        "b": {"type": "number"}
+                    },
+                    "required": ["a", "b"]
+                })),
+            },
+            Arc::new(|request| {
+                let result = request["a"].as_i64().unwrap() + request["b"].as_i64().unwrap();
+                json!({"result": result})
+            }),
+        );
+        Chat::new(
+            url.to_string(),
+            "test-key".to_string(),
+            "test-model".to_string(),
+            Arc::new(function_store),
+            HttpClient::default(),
+        )
+    }
+
+    fn session() -> Arc<Mutex<Session>> {
+        let mut session = Session {
+            functions: Some(vec!["add".to_string()]),
+            ..Session::default()
+        };
+        session
+            .add_message(Message::UserMessage("what is 1 + 2".to_string()))
+            .unwrap();
+        Arc::new(Mutex::new(session))
+    }
+
+    async fn stream(chat: &Chat, session: Arc<Mutex<Session>>) -> Result<String, Exception> {
+        let mut stream = chat.generate_stream(session).await?;
+        let mut text = String::new();
+        while let Some(chunk) = stream.next().await {
+            text.push_str(&chunk?);
+        }
+        Ok(text)
+    }
+
+    #[tokio::test]
+    async fn generate() {
+        let server = MockServer::start(vec![completion("3", 10, 1)]).await;
+        let session = session();
+
+        let content = chat(&server.url).generate(session.clone()).await.unwrap();
+
+        assert_eq!(content, "3");
+        let session = session.lock().unwrap();
+        assert_eq!(session.usage.prompt_tokens, 10);
+        assert_eq!(session.usage.completion_tokens, 1);
+        assert_eq!(session.messages.len(), 2);
+        let requests = server.requests();
+        assert_eq!(requests[0]["model"], "test-model");
+        assert_eq!(requests[0]["stream"], false);
+        assert_eq!(requests[0]["tools"][0]["function"]["name"], "add");
+    }
+
+    #[tokio::test]
+    async fn generate_with_tool_call() {
+        let server = MockServer::start(vec![
+            MockResponse::Json(json!({
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": {"name": "add", "arguments": "{\"a\": 1, \"b\": 2}"}
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }],
+                "usage": {"prompt_tokens": 20, "completion_tokens": 5, "total_tokens": 25}
+            })),
+            completion("3", 30, 1),
+        ])
+        .await;
+        let session = session();
+
+        let content = chat(&server.url).generate(session.clone()).await.unwrap();
+
+        assert_eq!(content, "3");
+        assert_eq!(session.lock().unwrap().usage.total_tokens, 56);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1]["messages"][1]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(requests[1]["messages"][2]["tool_call_id"], "call_1");
+        assert_eq!(requests[1]["messages"][2]["content"][0]["text"], "{\"result\":3}");
+    }
+
+    #[tokio::test]
+    async fn generate_stream() {
+        let server = MockServer::start(vec![MockResponse::Stream(vec![
+            content_chunk("Hel", None),
+            content_chunk("lo", None),
+            finish_chunk("stop"),
+            usage_chunk(5, 2),
+        ])])
+        .await;
+        let session = session();
+
+        let text = stream(&chat(&server.url), session.clone()).await.unwrap();
+
+        assert_eq!(text, "Hello\n");
+        let session = session.lock().unwrap();
+        assert_eq!(session.usage.prompt_tokens, 5);
+        assert_eq!(session.usage.completion_tokens, 2);
+        let requests = server.requests();
+        assert_eq!(requests[0]["stream"], true);
+        assert_eq!(requests[0]["stream_options"]["include_usage"], true);
+    }
+
+    #[tokio::test]
+    async fn generate_stream_with_split_tool_calls() {
+        let server = MockServer::start(vec![
+            MockResponse::Stream(vec![
+                tool_call_chunk(0, Some("call_1"), Some("add"), ""),
+                tool_call_chunk(0, None, None, "{\"a\": 1,"),
+                tool_call_chunk(1, Some("call_2"), Some("add"), "{\"a\""),
+                tool_call_chunk(0, None, None, " \"b\": 2}"),
+                tool_call_chunk(1, None, None, ": 3, \"b\": 4}"),
+                finish_chunk("tool_calls"),
+                usage_chunk(20, 5),
+            ]),
+            MockResponse::Stream(vec![content_chunk("3 and 7", Some("stop")), usage_chunk(40, 3)]),
+        ])
+        .await;
+        let session = session();
+
+        let text = stream(&chat(&server.url), session.clone()).await.unwrap();
+
+        assert_eq!(text, "3 and 7\n");
+        assert_eq!(session.lock().unwrap().usage.prompt_tokens, 60);
+        let requests = server.requests();
+        let messages = &requests[1]["messages"];
+        assert_eq!(
+            messages[1]["tool_calls"][0]["function"]["arguments"],
+            "{\"a\": 1, \"b\": 2}"
+        );
+        assert_eq!(
+            messages[1]["tool_calls"][1]["function"]["arguments"],
+            "{\"a\": 3, \"b\": 4}"
+        );
+        assert_eq!(messages[2]["tool_call_id"], "call_1");
+        assert_eq!(messages[2]["content"][0]["text"], "{\"result\":3}");
+        assert_eq!(messages[3]["tool_call_id"], "call_2");
+        assert_eq!(messages[3]["content"][0]["text"], "{\"result\":7}");
+    }
+
+    #[tokio::test]
+    async fn generate_with_error_status() {
+        let server = MockServer::start(vec![MockResponse::Error(500, "server error".to_string())]).await;
+
+        let result = chat(&server.url).generate(session()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn generate_stream_with_error_status() {
+        let server = MockServer::start(vec![MockResponse::Error(429, "rate limited".to_string())]).await;
+
+        let result = stream(&chat(&server.url), session()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn generate_stream_with_invalid_chunk() {
+        let server = MockServer::start(vec![MockResponse::Stream(vec![
+            content_chunk("Hel", None),
+            json!({"choices": "invalid"}),
+        ])])
+        .await;
+
+        let result = stream(&chat(&server.url), session()).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn record_and_replay_fixture() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("fixture.json");
+        let server = MockServer::start(vec![
+            MockResponse::Stream(vec![
+                tool_call_chunk(0, Some("call_1"), Some("add"), "{\"a\": 1,"),
+                tool_call_chunk(0, None, None, " \"b\": 2}"),
+                finish_chunk("tool_calls"),
+                usage_chunk(20, 5),
+            ]),
+            MockResponse::Stream(vec![content_chunk("3", Some("stop")), usage_chunk(30, 1)]),
+            completion("3", 10, 1),
+        ])
+        .await;
+
+        let recorder = chat(&format!("{}?api-key=secret", server.url)).with_fixture(Arc::new(Fixture::record(&path)));
+        let text = stream(&recorder, session()).await.unwrap();
+        let content = recorder.generate(session()).await.unwrap();
+        let fixture = fs::read_to_string(&path).unwrap();
+        assert!(!fixture.contains("secret"));
+
+        // nothing listens on port 1, replay must not call api
+        let replayer =
+            chat("http://127.0.0.1:1/chat/completions").with_fixture(Arc::new(Fixture::replay(&path).unwrap()));
+        assert_eq!(stream(&replayer, session()).await.unwrap(), text);
+        assert_eq!(replayer.generate(session()).await.unwrap(), content);
+        assert!(replayer.generate(session()).await.is_err());
+
+        let replayer =
+            chat("http://127.0.0.1:1/chat/completions").with_fixture(Arc::new(Fixture::replay(&path).unwrap()));
+        let session = session();
+        session
+            .lock()
+            .unwrap()
+            .add_message(Message::UserMessage("changed".to_string()))
+            .unwrap();
+        assert!(stream(&replayer, session).await.is_err());
+    }
+
+    // recorded from mock server modeled on azure openai stream, first chunk has prompt_filter_results without choices,
+    // role chunk has empty content, usage chunk has no choices
+    #[tokio::test]
+    async fn replay_azure_stream_fixture() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/azure_stream_tool_call.json");
+        let chat = chat("http://127.0.0.1:1/chat/completions").with_fixture(Arc::new(Fixture::replay(&path).unwrap()));
+        let session = session();
+
+        let text = stream(&chat, session.clone()).await.unwrap();
+
+        assert_eq!(text, "1 + 2 = 3\n");
+        let session = session.lock().unwrap();
+        assert_eq!(session.usage.prompt_tokens, 321);
+        assert_eq!(session.usage.completion_tokens, 96);
+        let calls = session.messages[1].tool_calls.as_ref().unwrap();
+        assert_eq!(calls[0].id, "call_AbC123");
+        assert_eq!(calls[0].function.arguments, "{\"a\":1,\"b\":2}");
+        assert_eq!(session.messages[2].tool_call_id.as_deref(), Some("call_AbC123"));
+    }
+
+    #[tokio::test]
+    async fn cache_deterministic_request() {
+        let directory = tempdir().unwrap();
+        let server = MockServer::start(vec![completion("3", 10, 1), completion("4", 10, 1)]).await;
+        let chat = chat(&server.url).with_cache(Arc::new(Cache::new(
+            directory.path().to_path_buf(),
+            Duration::from_secs(60),
+            false,
+        )));
+        let deterministic = || {
+            let session = session();
+            session.lock().unwrap().temperature = Some(0.0);
+            session
+        };
+
+        assert_eq!(chat.generate(deterministic()).await.unwrap(), "3");
+        assert_eq!(chat.generate(deterministic()).await.unwrap(), "3");
+        assert_eq!(server.requests().len(), 1);
+
+        // not cached if temperature is not 0
+        assert_eq!(chat.generate(session()).await.unwrap(), "4");
+        assert!(chat.generate(session()).await.is_err());
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cache_is_skipped_while_recording_fixture() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("fixture.json");
+        let server = MockServer::start(vec![completion("3", 10, 1), completion("3", 10, 1)]).await;
+        let chat = chat(&server.url)
+            .with_cache(Arc::new(Cache::new(
+                directory.path().join("cache"),
+                Duration::from_secs(60),
+                true,
+            )))
+            .with_fixture(Arc::new(Fixture::record(&path)));
+
+        assert_eq!(chat.generate(session()).await.unwrap(), "3");
+        assert_eq!(chat.generate(session()).await.unwrap(), "3");
+        let fixture: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(fixture["interactions"].as_array().unwrap().len(), 2);
+        assert!(!directory.path().join("cache").exists());
+    }
+
+    #[tokio::test]
+    async fn cache_expired_response() {
+        let directory = tempdir().unwrap();
+        let server = MockServer::start(vec![completion("3", 10, 1), completion("4", 10, 1)]).await;
+        let chat = chat(&server.url).with_cache(Arc::new(Cache::new(
+            directory.path().to_path_buf(),
+            Duration::ZERO,
+            true,
+        )));
+
+        assert_eq!(chat.generate(session()).await.unwrap(), "3");
+        assert_eq!(chat.generate(session()).await.unwrap(), "4");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn cache_hit_does_not_count_usage() {
+        let directory = tempdir().unwrap();
+        let server = MockServer::start(vec![completion("3", 10, 1)]).await;
+        let chat = chat(&server.url).with_cache(Arc::new(Cache::new(
+            directory.path().to_path_buf(),
+            Duration::from_secs(60),
+            true,
+        )));
+
+        let session1 = session();
+        assert_eq!(chat.generate(session1.clone()).await.unwrap(), "3");
+        let session2 = session();
+        assert_eq!(chat.generate(session2.clone()).await.unwrap(), "3");
+
+        assert_eq!(session1.lock().unwrap().usage.total_tokens, 11);
+        assert_eq!(session2.lock().unwrap().usage.total_tokens, 0);
+    }
+
+    #[tokio::test]
+    async fn cache_skips_tool_calls() {
+        let directory = tempdir().unwrap();
+        let tool_call = || {
+            MockResponse::Json(json!({
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "add", "arguments": "{\"a\": 1, \"b\": 2}"}}]
+                    },
+                    "finish_reason": "tool_calls"
+                }],
+                "usage": {"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}
+            }))
+        };
+        let server = MockServer::start(vec![tool_call(), completion("3", 20, 1), tool_call()]).await;
+        let chat = chat(&server.url).with_cache(Arc::new(Cache::new(
+            directory.path().to_path_buf(),
+            Duration::from_secs(60),
+            true,
+        )));
+
+        assert_eq!(chat.generate(session()).await.unwrap(), "3");
+        // tool call is requested again, answer after same tool result is from cache
+        assert_eq!(chat.generate(session()).await.unwrap(), "3");
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cache_treats_corrupt_entry_as_miss() {
+        let directory = tempdir().unwrap();
+        let server = MockServer::start(vec![completion("3", 10, 1), completion("4", 10, 1)]).await;
+        let chat = chat(&server.url).with_cache(Arc::new(Cache::new(
+            directory.path().to_path_buf(),
+            Duration::from_secs(60),
+            true,
+        )));
+
+        assert_eq!(chat.generate(session()).await.unwrap(), "3");
+        for entry in fs::read_dir(directory.path()).unwrap() {
+            fs::write(entry.unwrap().path(), "{\"type\": \"body\", \"sta").unwrap();
+        }
+        assert_eq!(chat.generate(session()).await.unwrap(), "4");
+        assert_eq!(chat.generate(session()).await.unwrap(), "4");
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn cache_key_includes_endpoint() {
+        let directory = tempdir().unwrap();
+        let cache = Arc::new(Cache::new(
+            directory.path().to_path_buf(),
+            Duration::from_secs(60),
+            true,
+        ));
+        let server1 = MockServer::start(vec![completion("3", 10, 1)]).await;
+        let server2 = MockServer::start(vec![completion("4", 10, 1)]).await;
+
+        let chat1 = chat(&server1.url).with_cache(cache.clone());
+        let chat2 = chat(&server2.url).with_cache(cache);
+        assert_eq!(chat1.generate(session()).await.unwrap(), "3");
+        assert_eq!(chat2.generate(session()).await.unwrap(), "4");
+    }
+}
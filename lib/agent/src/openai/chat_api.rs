@@ -3,7 +3,9 @@ use std::path::PathBuf;
 
 use base64::Engine;
 use base64::prelude::BASE64_STANDARD;
+use framework::exception;
 use framework::exception::Exception;
+use framework::fs::path::PathExt;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -32,7 +34,7 @@ pub struct ChatRequest {
     pub prediction: Option<Prediction>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChatRequestMessage {
     pub role: Role,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -43,57 +45,99 @@ pub struct ChatRequestMessage {
     pub tool_calls: Option<Vec<ToolCall>>,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Content {
-    pub r#type: &'static str,
+    pub r#type: ContentType,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_url: Option<ImageUrl>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file: Option<File>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_audio: Option<InputAudio>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum ContentType {
+    #[serde(rename = "text")]
+    Text,
+    #[serde(rename = "image_url")]
+    ImageUrl,
+    #[serde(rename = "file")]
+    File,
+    #[serde(rename = "input_audio")]
+    InputAudio,
 }
 
 impl Content {
     fn image(url: String) -> Self {
         Content {
-            r#type: "image_url",
+            r#type: ContentType::ImageUrl,
             text: None,
             image_url: Some(ImageUrl { url }),
             file: None,
+            input_audio: None,
         }
     }
 
     fn file(path: PathBuf) -> Result<Self, Exception> {
         Ok(Content {
-            r#type: "file",
+            r#type: ContentType::File,
             text: None,
             image_url: None,
             file: Some(File {
                 filename: path.file_name().unwrap().to_string_lossy().to_string(),
                 file_data: BASE64_STANDARD.encode(fs::read(&path)?),
             }),
+            input_audio: None,
+        })
+    }
+
+    fn audio(path: PathBuf) -> Result<Self, Exception> {
+        let format = match path.file_extension()? {
+            "wav" => Ok("wav"),
+            "mp3" => Ok("mp3"),
+            _ => Err(exception!(
+                message = format!("not supported audio extension, path={}", path.to_string_lossy())
+            )),
+        }?;
+        Ok(Content {
+            r#type: ContentType::InputAudio,
+            text: None,
+            image_url: None,
+            file: None,
+            input_audio: Some(InputAudio {
+                data: BASE64_STANDARD.encode(fs::read(&path)?),
+                format: format.to_string(),
+            }),
         })
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ImageUrl {
     pub url: String, // Either a URL of the image or the base64 encoded image data.
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct File {
     pub filename: String,
     pub file_data: String, // The base64 encoded file data, used when passing the file to the model as a string.
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InputAudio {
+    pub data: String, // The base64 encoded audio data.
+    pub format: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct StreamOptions {
     pub include_usage: bool,
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ResponseFormat {
     pub r#type: ResponseType,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -123,7 +167,7 @@ impl ResponseFormat {
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ResponseType {
     #[serde(rename = "text")]
     Text,
@@ -138,10 +182,11 @@ impl ChatRequestMessage {
         ChatRequestMessage {
             role,
             content: Some(vec![Content {
-                r#type: "text",
+                r#type: ContentType::Text,
                 text: Some(message),
                 image_url: None,
                 file: None,
+                input_audio: None,
             }]),
             tool_call_id: None,
             tool_calls: None,
@@ -170,20 +215,35 @@ impl ChatRequestMessage {
         })
     }
 
+    pub fn new_user_audios(paths: Vec<PathBuf>) -> Result<Self, Exception> {
+        let contents = paths
+            .into_iter()
+            .map(Content::audio)
+            .collect::<Result<Vec<Content>, Exception>>()?;
+        Ok(ChatRequestMessage {
+            role: Role::User,
+            content: Some(contents),
+            tool_call_id: None,
+            tool_calls: None,
+        })
+    }
+
     pub fn new_user_message(message: String, image_urls: Vec<String>) -> Self {
         let mut content = vec![];
         content.push(Content {
-            r#type: "text",
+            r#type: ContentType::Text,
             text: Some(message),
             image_url: None,
             file: None,
+            input_audio: None,
         });
         for url in image_urls {
             content.push(Content {
-                r#type: "image_url",
+                r#type: ContentType::ImageUrl,
                 text: None,
                 image_url: Some(ImageUrl { url }),
                 file: None,
+                input_audio: None,
             });
         }
         ChatRequestMessage {
@@ -198,10 +258,11 @@ impl ChatRequestMessage {
         ChatRequestMessage {
             role: Role::Tool,
             content: Some(vec![Content {
-                r#type: "text",
+                r#type: ContentType::Text,
                 text: Some(value),
                 image_url: None,
                 file: None,
+                input_audio: None,
             }]),
             tool_call_id: Some(id),
             tool_calls: None,
@@ -282,13 +343,21 @@ pub struct StreamFunctionCall {
     pub arguments: String,
 }
 
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Usage {
     pub prompt_tokens: i32,
     pub completion_tokens: i32,
     pub total_tokens: i32,
 }
 
+impl Usage {
+    pub fn add(&mut self, usage: &Usage) {
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.total_tokens += usage.total_tokens;
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ChatResponse {
     pub choices: Vec<ChatCompletionChoice>,
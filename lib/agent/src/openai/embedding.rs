@@ -0,0 +1,76 @@
+use framework::exception;
+use framework::exception::Exception;
+use framework::http::HttpClient;
+use framework::http::HttpMethod::POST;
+use framework::http::HttpRequest;
+use framework::json;
+use tracing::debug;
+
+use crate::openai;
+use crate::openai::embedding_api::EmbeddingRequest;
+use crate::openai::embedding_api::EmbeddingResponse;
+use crate::openai::embedding_api::EncodingFormat;
+
+pub struct Embedding {
+    url: String,
+    api_key: String,
+    model: String,
+    http_client: HttpClient,
+}
+
+impl Embedding {
+    pub fn new(url: String, api_key: String, model: String, http_client: HttpClient) -> Self {
+        Embedding {
+            url,
+            api_key,
+            model,
+            http_client,
+        }
+    }
+
+    // return one vector per input, in the same order as input
+    pub async fn encode(
+        &self,
+        input: Vec<String>,
+        dimensions: Option<i32>,
+        encoding_format: EncodingFormat,
+    ) -> Result<Vec<Vec<f32>>, Exception> {
+        let size = input.len();
+        let request = EmbeddingRequest {
+            model: self.model.to_string(),
+            input,
+            encoding_format,
+            dimensions,
+        };
+        let mut http_request = HttpRequest::new(POST, &self.url);
+        http_request.body(json::to_json(&request)?, "application/json");
+        openai::authorize(&mut http_request, &self.api_key);
+
+        let http_response = self.http_client.execute(http_request).await?;
+        if http_response.status != 200 {
+            return Err(exception!(
+                message = format!(
+                    "failed to call embedding api, status={}, response={}",
+                    http_response.status, http_response.body
+                )
+            ));
+        }
+        let response: EmbeddingResponse = json::from_json(&http_response.body)?;
+        debug!(
+            "usage, prompt_tokens={}, total_tokens={}",
+            response.usage.prompt_tokens, response.usage.total_tokens
+        );
+        if response.data.len() != size {
+            return Err(exception!(
+                message = format!(
+                    "embedding count mismatch, expected={size}, actual={}",
+                    response.data.len()
+                )
+            ));
+        }
+
+        let mut data = response.data;
+        data.sort_by_key(|data| data.index);
+        data.into_iter().map(|data| data.embedding.decode()).collect()
+    }
+}
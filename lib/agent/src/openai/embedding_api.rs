@@ -0,0 +1,96 @@
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use framework::exception;
+use framework::exception::Exception;
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: Vec<String>,
+    pub encoding_format: EncodingFormat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub enum EncodingFormat {
+    #[serde(rename = "float")]
+    Float,
+    #[serde(rename = "base64")]
+    Base64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingResponse {
+    pub data: Vec<EmbeddingData>,
+    pub usage: EmbeddingUsage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmbeddingData {
+    pub index: usize,
+    pub embedding: EmbeddingValue,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingValue {
+    Float(Vec<f32>),
+    Base64(String), // little-endian f32 array encoded with base64
+}
+
+impl EmbeddingValue {
+    pub fn decode(self) -> Result<Vec<f32>, Exception> {
+        match self {
+            EmbeddingValue::Float(values) => Ok(values),
+            EmbeddingValue::Base64(value) => {
+                let bytes = BASE64_STANDARD.decode(value)?;
+                if bytes.len() % 4 != 0 {
+                    return Err(exception!(
+                        message = format!("invalid base64 embedding, length={}", bytes.len())
+                    ));
+                }
+                Ok(bytes
+                    .chunks_exact(4)
+                    .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+                    .collect())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EmbeddingUsage {
+    pub prompt_tokens: i32,
+    pub total_tokens: i32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_base64() {
+        let values = [0.5f32, -1.25, 3.0];
+        let bytes: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+        let value: EmbeddingValue = serde_json::from_str(&format!("\"{}\"", BASE64_STANDARD.encode(bytes))).unwrap();
+
+        assert_eq!(value.decode().unwrap(), values);
+    }
+
+    #[test]
+    fn decode_float() {
+        let value: EmbeddingValue = serde_json::from_str("[0.5, -1.25]").unwrap();
+
+        assert_eq!(value.decode().unwrap(), [0.5, -1.25]);
+    }
+
+    #[test]
+    fn decode_invalid_base64_length() {
+        let value = EmbeddingValue::Base64(BASE64_STANDARD.encode([0u8; 6]));
+
+        assert!(value.decode().is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use framework::exception;
+use framework::exception::Exception;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+use serde_json::json;
+use uuid::Uuid;
+
+// record http interactions of chat to file, or replay them in order without network
+pub struct Fixture {
+    path: PathBuf,
+    mode: Mode,
+    interactions: Mutex<VecDeque<Interaction>>,
+}
+
+#[derive(PartialEq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Deserialize)]
+struct FixtureFile {
+    interactions: VecDeque<Interaction>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Interaction {
+    url: String, // api key in query is redacted, headers are not recorded
+    request: Value,
+    response: RecordedResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+// response of api, shared by fixture and cache
+pub(crate) enum RecordedResponse {
+    Body { status: u16, body: String },
+    Events { events: Vec<String> }, // sse data in order, including [DONE]
+}
+
+impl Fixture {
+    pub fn record(path: &Path) -> Self {
+        Fixture {
+            path: path.to_path_buf(),
+            mode: Mode::Record,
+            interactions: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn replay(path: &Path) -> Result<Self, Exception> {
+        let file: FixtureFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        Ok(Fixture {
+            path: path.to_path_buf(),
+            mode: Mode::Replay,
+            interactions: Mutex::new(file.interactions),
+        })
+    }
+
+    // PUPPET_FIXTURE=record:path or replay:path, e.g. to capture responses of real provider as test fixture
+    pub fn from_env() -> Result<Option<Self>, Exception> {
+        let Ok(value) = env::var("PUPPET_FIXTURE") else {
+            return Ok(None);
+        };
+        match value.split_once(':') {
+            Some(("record", path)) if !path.is_empty() => Ok(Some(Fixture::record(Path::new(path)))),
+            Some(("replay", path)) if !path.is_empty() => Ok(Some(Fixture::replay(Path::new(path))?)),
+            _ => Err(exception!(
+                message = format!("invalid PUPPET_FIXTURE, expected record:path or replay:path, value={value}")
+            )),
+        }
+    }
+
+    pub(crate) fn is_replay(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    // request must match recorded one, so changes of request serialization are caught as well
+    pub(crate) fn next(&self, body: &str) -> Result<RecordedResponse, Exception> {
+        let request: Value = serde_json::from_str(body)?;
+        let interaction = self.interactions.lock().unwrap().pop_front().ok_or_else(|| {
+            exception!(message = format!("no recorded interaction left, path={}", self.path.to_string_lossy()))
+        })?;
+        if interaction.request != request {
+            return Err(exception!(
+                message = format!(
+                    "request does not match recorded interaction, path={}, expected={}, actual={}",
+                    self.path.to_string_lossy(),
+                    interaction.request,
+                    request
+                )
+            ));
+        }
+        Ok(interaction.response)
+    }
+
+    // file is rewritten after every interaction via temp file and rename, it stays valid if process is interrupted
+    pub(crate) fn save(&self, url: &str, body: &str, response: RecordedResponse) -> Result<(), Exception> {
+        let mut interactions = self.interactions.lock().unwrap();
+        interactions.push_back(Interaction {
+            url: redact_url(url),
+            request: serde_json::from_str(body)?,
+            response,
+        });
+        let file = json!({ "interactions": *interactions });
+        let name = self.path.file_name().unwrap_or_default().to_string_lossy();
+        let temp_path = self.path.with_file_name(format!(".{name}.{}.tmp", Uuid::now_v7()));
+        fs::write(&temp_path, serde_json::to_string_pretty(&file)?)?;
+        if let Err(error) = fs::rename(&temp_path, &self.path) {
+            let _ = fs::remove_file(&temp_path);
+            return Err(error.into());
+        }
+        Ok(())
+    }
+}
+
+// e.g. azure style ?api-key=xxx
+fn redact_url(url: &str) -> String {
+    let Some((base, query)) = url.split_once('?') else {
+        return url.to_string();
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|param| match param.split_once('=') {
+            Some((name, _)) if name.to_lowercase().contains("key") => format!("{name}=***"),
+            _ => param.to_string(),
+        })
+        .collect();
+    format!("{base}?{}", query.join("&"))
+}
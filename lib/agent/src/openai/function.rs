@@ -33,18 +33,29 @@ impl FunctionStore {
         );
     }
 
-    pub fn definitions(&self, functions: &Option<Vec<String>>) -> Option<Vec<Tool>> {
+    pub fn definitions(&self, functions: &Option<Vec<String>>) -> Result<Option<Vec<Tool>>, Exception> {
         if let Some(functions) = functions {
             let mut definitions = Vec::with_capacity(functions.len());
             for function in functions {
-                definitions.push(self.definitions.get(function.as_str()).unwrap().clone());
+                let definition = self.definitions.get(function.as_str()).ok_or_else(|| {
+                    exception!(
+                        message = format!("function not found, function={function}, available={:?}", self.names())
+                    )
+                })?;
+                definitions.push(definition.clone());
             }
-            Some(definitions)
+            Ok(Some(definitions))
         } else {
-            None
+            Ok(None)
         }
     }
 
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.definitions.keys().copied().collect();
+        names.sort();
+        names
+    }
+
     pub fn call(&self, functions: Vec<FunctionPayload>) -> Result<Vec<FunctionPayload>, Exception> {
         let mut results = vec![];
         for function in functions {
@@ -0,0 +1,141 @@
+use std::path::Path;
+use std::path::PathBuf;
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use framework::exception;
+use framework::exception::Exception;
+use framework::fs::path::PathExt;
+use framework::http::HttpClient;
+use framework::http::HttpMethod::POST;
+use framework::http::HttpRequest;
+use framework::json;
+use tokio::fs;
+use tracing::debug;
+
+use crate::openai;
+use crate::openai::image_api::ImageGenerationRequest;
+use crate::openai::image_api::ImageResponse;
+use crate::openai::multipart;
+use crate::openai::multipart::Multipart;
+
+pub struct Image {
+    url: String, // url of /images/generations, /images/edits is derived from it
+    api_key: String,
+    model: String,
+    http_client: HttpClient,
+}
+
+#[derive(Default)]
+pub struct ImageOptions {
+    pub n: Option<i32>,
+    pub size: Option<String>,
+    pub quality: Option<String>,
+}
+
+impl Image {
+    pub fn new(url: String, api_key: String, model: String, http_client: HttpClient) -> Self {
+        Image {
+            url,
+            api_key,
+            model,
+            http_client,
+        }
+    }
+
+    // return png images
+    pub async fn generate(&self, prompt: &str, options: &ImageOptions) -> Result<Vec<Vec<u8>>, Exception> {
+        debug!("[image] generate: {prompt}");
+        let request = ImageGenerationRequest {
+            model: &self.model,
+            prompt,
+            n: options.n,
+            size: options.size.as_deref(),
+            quality: options.quality.as_deref(),
+        };
+        let mut http_request = HttpRequest::new(POST, &self.url);
+        http_request.body(json::to_json(&request)?, "application/json");
+        self.execute(http_request).await
+    }
+
+    // return png images, edited based on input images
+    pub async fn edit(
+        &self,
+        prompt: &str,
+        images: Vec<PathBuf>,
+        options: &ImageOptions,
+    ) -> Result<Vec<Vec<u8>>, Exception> {
+        debug!("[image] edit: {prompt}");
+        let url = edit_url(&self.url)?;
+        let mut form = Multipart::new();
+        form.text("model", &self.model);
+        form.text("prompt", prompt);
+        if let Some(n) = options.n {
+            form.text("n", &n.to_string());
+        }
+        if let Some(size) = &options.size {
+            form.text("size", size);
+        }
+        if let Some(quality) = &options.quality {
+            form.text("quality", quality);
+        }
+        for path in images {
+            debug!("[image] image: path={}", path.to_string_lossy());
+            let content_type = image_mime_type(&path)?;
+            let data = fs::read(&path).await?;
+            let filename = path.file_name().unwrap().to_string_lossy();
+            form.file("image[]", &filename, content_type, &data);
+        }
+
+        let mut http_request = HttpRequest::new(POST, &url);
+        http_request.body(form.finish(), multipart::CONTENT_TYPE);
+        self.execute(http_request).await
+    }
+
+    async fn execute(&self, mut http_request: HttpRequest) -> Result<Vec<Vec<u8>>, Exception> {
+        openai::authorize(&mut http_request, &self.api_key);
+
+        let http_response = self.http_client.execute(http_request).await?;
+        if http_response.status != 200 {
+            return Err(exception!(
+                message = format!(
+                    "failed to call image api, status={}, response={}",
+                    http_response.status, http_response.body
+                )
+            ));
+        }
+        let response: ImageResponse = json::from_json(&http_response.body)?;
+        let mut images = Vec::with_capacity(response.data.len());
+        for data in response.data {
+            if let Some(revised_prompt) = data.revised_prompt {
+                debug!("[image] revised_prompt: {revised_prompt}");
+            }
+            let b64_json = data
+                .b64_json
+                .ok_or_else(|| exception!(message = "image response must contain b64_json"))?;
+            images.push(BASE64_STANDARD.decode(b64_json)?);
+        }
+        Ok(images)
+    }
+}
+
+// edits api is next to generations api, e.g. /v1/images/generations -> /v1/images/edits
+fn edit_url(url: &str) -> Result<String, Exception> {
+    if !url.contains("/images/generations") {
+        return Err(exception!(
+            message = format!("image url must contain /images/generations to derive edits url, url={url}")
+        ));
+    }
+    Ok(url.replace("/images/generations", "/images/edits"))
+}
+
+fn image_mime_type(path: &Path) -> Result<&'static str, Exception> {
+    match path.file_extension()? {
+        "jpg" | "jpeg" => Ok("image/jpeg"),
+        "png" => Ok("image/png"),
+        "webp" => Ok("image/webp"),
+        _ => Err(exception!(
+            message = format!("not supported image extension, path={}", path.to_string_lossy())
+        )),
+    }
+}
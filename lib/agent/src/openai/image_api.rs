@@ -0,0 +1,25 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct ImageGenerationRequest<'a> {
+    pub model: &'a str,
+    pub prompt: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageResponse {
+    pub data: Vec<ImageData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImageData {
+    pub b64_json: Option<String>,
+    pub revised_prompt: Option<String>,
+}
@@ -0,0 +1,141 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use axum::Json;
+use axum::Router;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::http::header;
+use axum::response::IntoResponse;
+use axum::response::Response;
+use axum::routing::post;
+use serde_json::Value;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+// scripted openai compatible server, serves responses in order and records requests
+pub(crate) struct MockServer {
+    pub(crate) url: String,
+    state: MockState,
+}
+
+#[derive(Clone, Default)]
+struct MockState {
+    responses: Arc<Mutex<VecDeque<MockResponse>>>,
+    requests: Arc<Mutex<Vec<Value>>>,
+}
+
+pub(crate) enum MockResponse {
+    Json(Value),
+    Stream(Vec<Value>), // chunks sent as sse data, followed by [DONE]
+    Error(u16, String),
+}
+
+impl MockServer {
+    pub(crate) async fn start(responses: Vec<MockResponse>) -> Self {
+        let state = MockState {
+            responses: Arc::new(Mutex::new(responses.into())),
+            ..MockState::default()
+        };
+        let app = Router::new()
+            .route("/chat/completions", post(chat_completions))
+            .with_state(state.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        MockServer {
+            url: format!("http://{address}/chat/completions"),
+            state,
+        }
+    }
+
+    pub(crate) fn requests(&self) -> Vec<Value> {
+        self.state.requests.lock().unwrap().clone()
+    }
+}
+
+async fn chat_completions(State(state): State<MockState>, Json(request): Json<Value>) -> Response {
+    state.requests.lock().unwrap().push(request);
+    let response = state.responses.lock().unwrap().pop_front();
+    match response {
+        Some(MockResponse::Json(body)) => Json(body).into_response(),
+        Some(MockResponse::Stream(chunks)) => {
+            let mut body = String::new();
+            for chunk in chunks {
+                body.push_str(&format!("data: {chunk}\n\n"));
+            }
+            body.push_str("data: [DONE]\n\n");
+            ([(header::CONTENT_TYPE, "text/event-stream")], body).into_response()
+        }
+        Some(MockResponse::Error(status, message)) => (
+            StatusCode::from_u16(status).unwrap(),
+            Json(json!({"error": {"message": message}})),
+        )
+            .into_response(),
+        None => (StatusCode::INTERNAL_SERVER_ERROR, "no scripted response left").into_response(),
+    }
+}
+
+pub(crate) fn completion(content: &str, prompt_tokens: i32, completion_tokens: i32) -> MockResponse {
+    MockResponse::Json(json!({
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": content},
+            "finish_reason": "stop"
+        }],
+        "usage": usage(prompt_tokens, completion_tokens)
+    }))
+}
+
+pub(crate) fn content_chunk(content: &str, finish_reason: Option<&str>) -> Value {
+    json!({
+        "choices": [{
+            "index": 0,
+            "delta": {"content": content},
+            "finish_reason": finish_reason
+        }],
+        "usage": null
+    })
+}
+
+pub(crate) fn tool_call_chunk(index: i64, id: Option<&str>, name: Option<&str>, arguments: &str) -> Value {
+    json!({
+        "choices": [{
+            "index": 0,
+            "delta": {
+                "tool_calls": [{
+                    "index": index,
+                    "id": id,
+                    "type": "function",
+                    "function": {"name": name, "arguments": arguments}
+                }]
+            },
+            "finish_reason": null
+        }],
+        "usage": null
+    })
+}
+
+pub(crate) fn finish_chunk(finish_reason: &str) -> Value {
+    json!({
+        "choices": [{"index": 0, "delta": {}, "finish_reason": finish_reason}],
+        "usage": null
+    })
+}
+
+// sent as last chunk when stream_options.include_usage is true
+pub(crate) fn usage_chunk(prompt_tokens: i32, completion_tokens: i32) -> Value {
+    json!({
+        "choices": [],
+        "usage": usage(prompt_tokens, completion_tokens)
+    })
+}
+
+fn usage(prompt_tokens: i32, completion_tokens: i32) -> Value {
+    json!({
+        "prompt_tokens": prompt_tokens,
+        "completion_tokens": completion_tokens,
+        "total_tokens": prompt_tokens + completion_tokens
+    })
+}
@@ -0,0 +1,37 @@
+// boundary is fixed so content type can be static, it is long enough not to appear in uploaded files
+const BOUNDARY: &str = "puppet-multipart-boundary-5f0c7b1e9d3a";
+pub(crate) const CONTENT_TYPE: &str = "multipart/form-data; boundary=puppet-multipart-boundary-5f0c7b1e9d3a";
+
+pub(crate) struct Multipart {
+    body: Vec<u8>,
+}
+
+impl Multipart {
+    pub(crate) fn new() -> Self {
+        Multipart { body: vec![] }
+    }
+
+    pub(crate) fn text(&mut self, name: &str, value: &str) {
+        self.body.extend_from_slice(
+            format!("--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n").as_bytes(),
+        );
+        self.body.extend_from_slice(value.as_bytes());
+        self.body.extend_from_slice(b"\r\n");
+    }
+
+    pub(crate) fn file(&mut self, name: &str, filename: &str, content_type: &str, data: &[u8]) {
+        self.body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"; filename=\"{filename}\"\r\nContent-Type: {content_type}\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        self.body.extend_from_slice(data);
+        self.body.extend_from_slice(b"\r\n");
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        self.body.extend_from_slice(format!("--{BOUNDARY}--\r\n").as_bytes());
+        self.body
+    }
+}
@@ -8,13 +8,22 @@ use base64::prelude::BASE64_STANDARD;
 use framework::exception;
 use framework::exception::Exception;
 use framework::fs::path::PathExt;
+use framework::json;
+use serde::Deserialize;
+use serde::Serialize;
 use tracing::debug;
+use tracing::info;
 
 use crate::openai::chat_api::ChatRequestMessage;
 use crate::openai::chat_api::ResponseFormat;
 use crate::openai::chat_api::Role;
+use crate::openai::chat_api::ToolCall;
+use crate::openai::chat_api::Usage;
 
-#[derive(Default)]
+// increase when session file format changes incompatibly
+const SESSION_VERSION: i32 = 1;
+
+#[derive(Default, Serialize, Deserialize)]
 pub struct Session {
     pub(crate) messages: Vec<ChatRequestMessage>,
     pub functions: Option<Vec<String>>,
@@ -22,6 +31,13 @@ pub struct Session {
     pub temperature: Option<f32>,
     pub response_format: Option<ResponseFormat>,
     pub max_completion_tokens: Option<i32>,
+    pub usage: Usage, // accumulated usage of all requests in this session
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionFile<T> {
+    version: i32,
+    session: T,
 }
 
 pub enum Message {
@@ -30,9 +46,46 @@ pub enum Message {
     AssistantMessage(String),
     Images(Vec<PathBuf>),
     Files(Vec<PathBuf>),
+    Audio(Vec<PathBuf>),
+    ToolCalls(Vec<ToolCall>),
+    ToolResult { id: String, value: String },
 }
 
 impl Session {
+    pub fn load(path: &Path) -> Result<Session, Exception> {
+        info!("load session, path={}", path.to_string_lossy());
+        let file: SessionFile<serde_json::Value> = json::load_file(path)?;
+        if file.version != SESSION_VERSION {
+            return Err(exception!(
+                message = format!(
+                    "not supported session version, path={}, version={}",
+                    path.to_string_lossy(),
+                    file.version
+                )
+            ));
+        }
+        Ok(serde_json::from_value(file.session)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Exception> {
+        info!("save session, path={}", path.to_string_lossy());
+        let file = SessionFile {
+            version: SESSION_VERSION,
+            session: self,
+        };
+        fs::write(path, json::to_json(&file)?)?;
+        Ok(())
+    }
+
+    pub fn messages(&self) -> &[ChatRequestMessage] {
+        &self.messages
+    }
+
+    // discard messages of failed turn, so next request does not send them
+    pub fn truncate_messages(&mut self, len: usize) {
+        self.messages.truncate(len);
+    }
+
     pub fn add_message(&mut self, message: Message) -> Result<(), Exception> {
         self.messages.push(match message {
             Message::SystemMessage(value) => {
@@ -61,6 +114,24 @@ impl Session {
                 debug!("[chat] files: paths={path_values:?}");
                 ChatRequestMessage::new_user_files(paths)?
             }
+            Message::Audio(paths) => {
+                let path_values: Vec<Cow<str>> = paths.iter().map(|path| path.to_string_lossy()).collect();
+                debug!("[chat] audio: paths={path_values:?}");
+                ChatRequestMessage::new_user_audios(paths)?
+            }
+            Message::ToolCalls(calls) => {
+                for call in calls.iter() {
+                    debug!(
+                        function_id = call.id,
+                        "[chat] function_call: {}({})", call.function.name, call.function.arguments
+                    );
+                }
+                ChatRequestMessage::new_function_call(calls)
+            }
+            Message::ToolResult { id, value } => {
+                debug!(function_id = id, "[chat] function_result: {value}");
+                ChatRequestMessage::new_function_response(id, value)
+            }
         });
         Ok(())
     }
@@ -79,3 +150,69 @@ fn base64_image_url(path: &Path) -> Result<String, Exception> {
     }?;
     Ok(format!("data:{mime_type};base64,{}", BASE64_STANDARD.encode(content)))
 }
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::openai::chat_api::FunctionCall;
+
+    #[test]
+    fn save_and_load() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("session.json");
+        let mut session = Session {
+            functions: Some(vec!["close_door".to_string()]),
+            temperature: Some(0.0),
+            top_p: Some(0.9),
+            response_format: Some(ResponseFormat::json()),
+            max_completion_tokens: Some(100),
+            usage: Usage {
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                total_tokens: 15,
+            },
+            ..Session::default()
+        };
+        session
+            .add_message(Message::SystemMessage("you are a helpful assistant".to_string()))
+            .unwrap();
+        session
+            .add_message(Message::UserMessage("close the door".to_string()))
+            .unwrap();
+        session
+            .add_message(Message::ToolCalls(vec![ToolCall {
+                id: "call_1".to_string(),
+                r#type: "function".to_string(),
+                function: FunctionCall {
+                    name: "close_door".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }]))
+            .unwrap();
+        session
+            .add_message(Message::ToolResult {
+                id: "call_1".to_string(),
+                value: "{\"success\":true}".to_string(),
+            })
+            .unwrap();
+        session
+            .add_message(Message::AssistantMessage("door is closed".to_string()))
+            .unwrap();
+
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path).unwrap();
+        assert_eq!(json::to_json(&loaded).unwrap(), json::to_json(&session).unwrap());
+        assert_eq!(loaded.messages().len(), 5);
+    }
+
+    #[test]
+    fn load_unsupported_version() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("session.json");
+        fs::write(&path, r#"{"version": 0, "session": {}}"#).unwrap();
+
+        assert!(Session::load(&path).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use framework::exception;
+use framework::exception::Exception;
+use framework::fs::path::PathExt;
+use framework::http::HttpClient;
+use framework::http::HttpMethod::POST;
+use framework::http::HttpRequest;
+use framework::json;
+use serde::Deserialize;
+use tokio::fs;
+use tracing::debug;
+
+use crate::openai;
+use crate::openai::multipart;
+use crate::openai::multipart::Multipart;
+
+pub struct Transcription {
+    url: String,
+    api_key: String,
+    model: String,
+    http_client: HttpClient,
+}
+
+#[derive(Debug, Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+}
+
+impl Transcription {
+    pub fn new(url: String, api_key: String, model: String, http_client: HttpClient) -> Self {
+        Transcription {
+            url,
+            api_key,
+            model,
+            http_client,
+        }
+    }
+
+    pub async fn transcribe(
+        &self,
+        path: &Path,
+        language: Option<&str>,
+        prompt: Option<&str>,
+    ) -> Result<String, Exception> {
+        debug!("[transcription] audio: path={}", path.to_string_lossy());
+        let content_type = audio_mime_type(path)?;
+        let data = fs::read(path).await?;
+
+        let mut form = Multipart::new();
+        form.text("model", &self.model);
+        form.text("response_format", "json");
+        if let Some(language) = language {
+            form.text("language", language);
+        }
+        if let Some(prompt) = prompt {
+            form.text("prompt", prompt);
+        }
+        let filename = path.file_name().unwrap().to_string_lossy();
+        form.file("file", &filename, content_type, &data);
+
+        let mut http_request = HttpRequest::new(POST, &self.url);
+        http_request.body(form.finish(), multipart::CONTENT_TYPE);
+        openai::authorize(&mut http_request, &self.api_key);
+
+        let http_response = self.http_client.execute(http_request).await?;
+        if http_response.status != 200 {
+            return Err(exception!(
+                message = format!(
+                    "failed to call transcription api, status={}, response={}",
+                    http_response.status, http_response.body
+                )
+            ));
+        }
+        let response: TranscriptionResponse = json::from_json(&http_response.body)?;
+        debug!("[transcription] text: {}", response.text);
+        Ok(response.text)
+    }
+}
+
+fn audio_mime_type(path: &Path) -> Result<&'static str, Exception> {
+    match path.file_extension()? {
+        "mp3" | "mpga" | "mpeg" => Ok("audio/mpeg"),
+        "mp4" | "m4a" => Ok("audio/mp4"),
+        "wav" => Ok("audio/wav"),
+        "webm" => Ok("audio/webm"),
+        "ogg" => Ok("audio/ogg"),
+        "flac" => Ok("audio/flac"),
+        _ => Err(exception!(
+            message = format!("not supported audio extension, path={}", path.to_string_lossy())
+        )),
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use framework::exception;
+use framework::exception::Exception;
+use framework::json;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::speech::azure::AzureTTS;
+use crate::speech::gcloud::GCloudTTS;
+use crate::speech::openai::OpenAITTS;
+
+pub mod azure;
+pub mod gcloud;
+pub mod openai;
+
+#[derive(Deserialize, Debug)]
+struct Config {
+    models: HashMap<String, ModelConfig>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelConfig {
+    endpoint: String,
+    provider: Provider,
+    params: HashMap<String, String>,
+}
+
+#[derive(Deserialize, Debug)]
+enum Provider {
+    #[serde(rename = "azure")]
+    Azure,
+    #[serde(rename = "gcloud")]
+    GCloud,
+    #[serde(rename = "openai")]
+    OpenAI,
+}
+
+impl ModelConfig {
+    fn param(&self, name: &str) -> Result<String, Exception> {
+        let value = self
+            .params
+            .get(name)
+            .ok_or_else(|| exception!(message = format!("config param {name} is required")))?;
+        Ok(value.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioFormat {
+    Wav,
+    Mp3,
+}
+
+impl AudioFormat {
+    pub fn from_extension(extension: &str) -> Result<Self, Exception> {
+        match extension {
+            "wav" => Ok(AudioFormat::Wav),
+            "mp3" => Ok(AudioFormat::Mp3),
+            _ => Err(exception!(
+                message = format!("not supported audio format, extension={extension}")
+            )),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Mp3 => "mp3",
+        }
+    }
+}
+
+pub enum Speech {
+    Azure(AzureTTS),
+    GCloud(GCloudTTS),
+    OpenAI(OpenAITTS),
+}
+
+impl Speech {
+    pub async fn synthesize(&self, text: &str, format: AudioFormat) -> Result<Vec<u8>, Exception> {
+        match self {
+            Speech::Azure(model) => model.synthesize(text, format).await,
+            Speech::GCloud(model) => model.synthesize(text, format).await,
+            Speech::OpenAI(model) => model.synthesize(text, format).await,
+        }
+    }
+}
+
+pub fn load(path: &Path, name: &str) -> Result<Speech, Exception> {
+    info!("load config, path={}", path.to_string_lossy());
+    let config: Config = json::load_file(path)?;
+
+    let config = config
+        .models
+        .get(name)
+        .ok_or_else(|| exception!(message = format!("can not find model, name={name}")))?;
+
+    let speech = match config.provider {
+        Provider::Azure => Speech::Azure(AzureTTS {
+            endpoint: config.endpoint.to_string(),
+            resource: config.param("resource")?,
+            api_key: config.param("api_key")?,
+            voice: config.param("voice")?,
+            http_client: AudioClient::default(),
+        }),
+        Provider::GCloud => Speech::GCloud(GCloudTTS {
+            endpoint: config.endpoint.to_string(),
+            project: config.param("project")?,
+            voice: config.param("voice")?,
+            http_client: AudioClient::default(),
+        }),
+        Provider::OpenAI => Speech::OpenAI(OpenAITTS {
+            endpoint: config.endpoint.to_string(),
+            api_key: config.param("api_key")?,
+            model: config.param("model")?,
+            voice: config.param("voice")?,
+            instructions: config.params.get("instructions").cloned(),
+            http_client: AudioClient::default(),
+        }),
+    };
+    Ok(speech)
+}
+
+// framework http client reads response body as text, audio must be read as raw bytes, all providers use this client
+#[derive(Default, Clone)]
+pub struct AudioClient {
+    client: reqwest::Client,
+}
+
+impl AudioClient {
+    async fn post(
+        &self,
+        provider: &str,
+        url: &str,
+        headers: Vec<(&'static str, String)>,
+        body: String,
+    ) -> Result<Vec<u8>, Exception> {
+        let mut request = self.client.post(url).body(body);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        let response = request.send().await?;
+        let status = response.status().as_u16();
+        if status != 200 {
+            return Err(exception!(
+                message = format!(
+                    "failed to call {provider} api, status={status}, response={}",
+                    response.text().await?
+                )
+            ));
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+}
@@ -0,0 +1,44 @@
+use framework::exception::Exception;
+use tracing::info;
+
+use crate::speech::AudioClient;
+use crate::speech::AudioFormat;
+
+pub struct AzureTTS {
+    pub endpoint: String,
+    pub resource: String,
+    pub api_key: String,
+    pub voice: String,
+    pub http_client: AudioClient,
+}
+
+impl AzureTTS {
+    pub async fn synthesize(&self, text: &str, format: AudioFormat) -> Result<Vec<u8>, Exception> {
+        info!("call azure synthesize api, endpoint={}", self.endpoint);
+        let body = format!(
+            r#"<speak version="1.0" xmlns="http://www.w3.org/2001/10/synthesis" xmlns:mstts="https://www.w3.org/2001/mstts" xml:lang="en-US">
+                <voice name="{}"><mstts:express-as style="narration-relaxed"><![CDATA[
+            {}
+            ]]></mstts:express-as></voice></speak>"#,
+            self.voice,
+            escape_cdata(text)
+        );
+        let output_format = match format {
+            AudioFormat::Wav => "riff-44100hz-16bit-mono-pcm",
+            AudioFormat::Mp3 => "audio-24khz-96kbitrate-mono-mp3",
+        };
+
+        let headers = vec![
+            ("content-type", "application/ssml+xml".to_string()),
+            ("ocp-apim-subscription-key", self.api_key.to_string()),
+            ("user-agent", self.resource.to_string()),
+            ("x-microsoft-outputformat", output_format.to_string()),
+        ];
+        self.http_client.post("azure", &self.endpoint, headers, body).await
+    }
+}
+
+// ]]> ends cdata section, split it into two sections
+fn escape_cdata(text: &str) -> String {
+    text.replace("]]>", "]]]]><![CDATA[>")
+}
@@ -0,0 +1,109 @@
+use std::borrow::Cow;
+
+use base64::Engine;
+use base64::prelude::BASE64_STANDARD;
+use framework::exception;
+use framework::exception::Exception;
+use framework::json;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::process::Command;
+use tracing::info;
+
+use crate::speech::AudioClient;
+use crate::speech::AudioFormat;
+
+pub struct GCloudTTS {
+    pub endpoint: String,
+    pub project: String,
+    pub voice: String,
+    pub http_client: AudioClient,
+}
+
+impl GCloudTTS {
+    pub async fn synthesize(&self, text: &str, format: AudioFormat) -> Result<Vec<u8>, Exception> {
+        info!("call gcloud synthesize api, endpoint={}", self.endpoint);
+        let request = SynthesizeRequest {
+            audio_config: AudioConfig {
+                audio_encoding: match format {
+                    AudioFormat::Wav => "LINEAR16",
+                    AudioFormat::Mp3 => "MP3",
+                },
+                effects_profile_id: vec!["headphone-class-device"],
+                pitch: 0,
+                speaking_rate: 1,
+            },
+            input: Input { text: Cow::from(text) },
+            voice: Voice {
+                language_code: "en-US",
+                name: Cow::from(&self.voice),
+            },
+        };
+
+        let headers = vec![
+            ("content-type", "application/json".to_string()),
+            ("authorization", format!("Bearer {}", token().await?)),
+            ("x-goog-user-project", self.project.to_string()),
+        ];
+        let body = self
+            .http_client
+            .post("gcloud", &self.endpoint, headers, json::to_json(&request)?)
+            .await?;
+        let response: SynthesizeResponse = serde_json::from_slice(&body)?;
+        Ok(BASE64_STANDARD.decode(response.audio_content)?)
+    }
+}
+
+// use access token of current gcloud cli login
+async fn token() -> Result<String, Exception> {
+    let output = Command::new("gcloud")
+        .args(["auth", "print-access-token"])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(exception!(
+            message = format!(
+                "failed to get gcloud access token, error={}",
+                String::from_utf8_lossy(&output.stderr)
+            )
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[derive(Debug, Serialize)]
+struct SynthesizeRequest<'a> {
+    #[serde(rename = "audioConfig")]
+    audio_config: AudioConfig,
+    input: Input<'a>,
+    voice: Voice<'a>,
+}
+
+#[derive(Debug, Serialize)]
+struct AudioConfig {
+    #[serde(rename = "audioEncoding")]
+    audio_encoding: &'static str,
+    #[serde(rename = "effectsProfileId")]
+    effects_profile_id: Vec<&'static str>,
+    pitch: i64,
+    #[serde(rename = "speakingRate")]
+    speaking_rate: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct Input<'a> {
+    text: Cow<'a, str>,
+}
+
+#[derive(Debug, Serialize)]
+struct Voice<'a> {
+    #[serde(rename = "languageCode")]
+    language_code: &'static str,
+    name: Cow<'a, str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SynthesizeResponse {
+    #[serde(rename = "audioContent")]
+    audio_content: String,
+}
@@ -0,0 +1,48 @@
+use framework::exception::Exception;
+use framework::json;
+use serde::Serialize;
+use tracing::info;
+
+use crate::openai;
+use crate::speech::AudioClient;
+use crate::speech::AudioFormat;
+
+pub struct OpenAITTS {
+    pub endpoint: String,
+    pub api_key: String,
+    pub model: String,
+    pub voice: String,
+    pub instructions: Option<String>,
+    pub http_client: AudioClient,
+}
+
+impl OpenAITTS {
+    pub async fn synthesize(&self, text: &str, format: AudioFormat) -> Result<Vec<u8>, Exception> {
+        info!("call openai speech api, endpoint={}", self.endpoint);
+        let request = SpeechRequest {
+            model: &self.model,
+            input: text,
+            voice: &self.voice,
+            instructions: self.instructions.as_deref(),
+            response_format: format.extension(),
+        };
+
+        let headers = vec![
+            ("content-type", "application/json".to_string()),
+            openai::auth_header(&self.api_key),
+        ];
+        self.http_client
+            .post("openai", &self.endpoint, headers, json::to_json(&request)?)
+            .await
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct SpeechRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+    voice: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<&'a str>,
+    response_format: &'static str,
+}